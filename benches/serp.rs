@@ -0,0 +1,43 @@
+//! Timings for building search URLs and parsing responses, run with `cargo bench`
+//! criterion is not a dependency, so this is a plain timing loop: compare numbers from the same machine only
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use scale_serp::search::{Params, Resp};
+
+/// run f repeatedly for about a second and print the mean time per call
+fn bench(name: &str, mut f: impl FnMut()) {
+    // warm up, and size the batches so each takes roughly 10ms
+    let start = Instant::now();
+    let mut batch = 0u64;
+    while start.elapsed() < Duration::from_millis(10) {
+        f();
+        batch += 1;
+    }
+    let mut calls = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        for _ in 0..batch {
+            f();
+        }
+        calls += batch;
+    }
+    let per_call = start.elapsed().as_nanos() / u128::from(calls);
+    println!("{:<40} {:>12} ns/iter ({} iterations)", name, per_call, calls);
+}
+
+fn main() {
+    let params = Params::new("demo", "best external ssd for video editing", "Austin,Texas,United States")
+        .with_gl("us")
+        .with_hl("en")
+        .with_google_domain("google.com")
+        .with_num(100);
+    bench("Params::to_url", || {
+        black_box(black_box(&params).to_url());
+    });
+
+    let json = include_str!("../fixtures/search_hundred_results.json");
+    bench("serde_json::from_str::<Resp> (100 results)", || {
+        black_box(serde_json::from_str::<Resp>(black_box(json)).unwrap());
+    });
+}
@@ -0,0 +1,8 @@
+#![no_main]
+// deserializing arbitrary bytes may fail, but must never panic
+use libfuzzer_sys::fuzz_target;
+use scale_serp::search::OrganicResult;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<OrganicResult>(data);
+});
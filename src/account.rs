@@ -0,0 +1,51 @@
+use serde::{Serialize, Deserialize};
+
+/// The response from the account endpoint
+/// Calls to this endpoint do not use any credits
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct AccountResp {
+    pub account_info: AccountInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct AccountInfo {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub plan: String,
+    pub credits_used: usize,
+    pub credits_limit: usize,
+    pub credits_remaining: usize,
+    #[serde(default)]
+    pub credits_reset_at: String, // i.e. '2021-07-31T01:00:37.000Z'
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_account() {
+        let json = r#"{
+            "request_info": {"success": true},
+            "account_info": {
+                "api_key": "demo",
+                "name": "Jane Doe",
+                "email": "jane@example.com",
+                "plan": "Basic",
+                "credits_used": 1250,
+                "credits_limit": 5000,
+                "credits_remaining": 3750,
+                "credits_reset_at": "2021-07-31T01:00:37.000Z"
+            }
+        }"#;
+        let resp: AccountResp = serde_json::from_str(json).unwrap();
+        assert_eq!(resp.account_info.credits_remaining, 3750);
+        assert_eq!(resp.account_info.plan, "Basic");
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use crate::dialect::ResponseDialect;
+use crate::error::Error;
+use crate::search::Resp;
+
+/// The file name a response is archived under
+/// The name is a hash of the query, location, page and creation timestamp, so saving the same response twice
+/// overwrites the same file while two different searches never share a name.
+/// The hash is 64-bit FNV-1a, so names stay the same across Rust releases and platforms
+pub fn file_name(resp: &Resp) -> String {
+    let page = resp.search_parameters.page.unwrap_or(1).to_string();
+    let parts = [resp.search_parameters.q.as_str(), &resp.search_parameters.location, &page, &resp.search_metadata.created_at];
+    format!("{:016x}.json", stable_hash(&parts))
+}
+
+/// 64-bit FNV-1a over parts, the same on every Rust release and platform so it can name files
+pub(crate) fn stable_hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        // 0xff never occurs in UTF-8, so it separates the parts unambiguously
+        for byte in part.bytes().chain([0xff]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Save a response as JSON in dir, returning the path written to
+/// The response is written to a temporary file first and then renamed, so readers never see a partial file
+pub fn save(resp: &Resp, dir: &Path) -> Result<PathBuf, Error> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(file_name(resp));
+    let tmp_path = path.with_extension("json.tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        serde_json::to_writer(&mut file, resp)?;
+        file.flush()?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, &path)?;
+    Ok(path)
+}
+
+/// Save a response like save, recording the dialect it was fetched under next to it
+/// The dialect goes in a ".dialect" file with the same name, so archives written by save still load
+pub fn save_with_dialect(resp: &Resp, dialect: ResponseDialect, dir: &Path) -> Result<PathBuf, Error> {
+    let path = save(resp, dir)?;
+    fs::write(path.with_extension("dialect"), dialect.as_str())?;
+    Ok(path)
+}
+
+/// The dialect a response saved at path was fetched under, None if it was saved without one
+pub fn load_dialect(path: &Path) -> Result<Option<ResponseDialect>, Error> {
+    match fs::read_to_string(path.with_extension("dialect")) {
+        Ok(name) => Ok(ResponseDialect::from_name(&name)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Load a response previously written by save
+pub fn load(path: &Path) -> Result<Resp, Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let resp: Resp = serde_json::from_reader(reader)?;
+    Ok(resp)
+}
+
+/// Lazily load every archived response in dir
+/// Responses are only read from disk as the iterator is advanced. Leftover temporary files are skipped
+pub fn iter(dir: &Path) -> Result<impl Iterator<Item = Result<Resp, Error>>, Error> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    Ok(paths.into_iter().map(|path| load(&path)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Resp {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn save_load_and_iter() {
+        let dir = std::env::temp_dir().join(format!("scale_serp_archive_{}", std::process::id()));
+        let resp = fixture();
+        let path = save(&resp, &dir).unwrap();
+        assert_eq!(path, dir.join(file_name(&resp)));
+        // saving the same response again must not create a second file
+        assert_eq!(save(&resp, &dir).unwrap(), path);
+
+        let mut other = fixture();
+        other.search_parameters.q = "internal ssd".to_string();
+        let other_path = save(&other, &dir).unwrap();
+        assert_ne!(other_path, path);
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.search_parameters.q, "external ssd");
+        assert_eq!(loaded.organic_results.len(), resp.organic_results.len());
+
+        assert_eq!(load_dialect(&path).unwrap(), None);
+        let path = save_with_dialect(&resp, ResponseDialect::V1Strict, &dir).unwrap();
+        assert_eq!(load_dialect(&path).unwrap(), Some(ResponseDialect::V1Strict));
+
+        let all: Vec<Resp> = iter(&dir).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(all.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(load(&path), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn stable_file_names() {
+        // pinned, so a change to the hash that would rename existing archives fails here
+        assert_eq!(file_name(&fixture()), "56137274911e6244.json");
+
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+        json["search_parameters"]["page"] = "1".into();
+        let first_page: Resp = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(file_name(&first_page), file_name(&fixture()));
+        json["search_parameters"]["page"] = "2".into();
+        let second_page: Resp = serde_json::from_value(json).unwrap();
+        assert_eq!(second_page.search_parameters.page, Some(2));
+        assert_ne!(file_name(&second_page), file_name(&fixture()));
+    }
+
+    #[test]
+    fn missing_blocks_stay_missing() {
+        let dir = std::env::temp_dir().join(format!("scale_serp_archive_blocks_{}", std::process::id()));
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+        json.as_object_mut().unwrap().remove("top_stories");
+        json["ads"] = serde_json::json!([]);
+        let resp: Resp = serde_json::from_value(json).unwrap();
+
+        let loaded = load(&save(&resp, &dir).unwrap()).unwrap();
+        assert!(!loaded.had_top_stories_block());
+        // an empty block that was in the payload is still there
+        assert!(loaded.had_ads_block() && loaded.ads.is_empty());
+        assert!(loaded.had_top_products_block() && loaded.had_related_questions_block());
+        assert!(serde_json::Value::from(&resp).get("top_stories").is_none());
+        assert!(!resp.pruned(&Default::default()).had_top_stories_block());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,15 @@
+/// A page authority score that can be plugged into the result helpers
+/// The crate does not ship a scorer: wrap whichever provider (Moz, Ahrefs, Majestic etc.) you already use
+pub trait PageAuthority {
+    /// the authority of the page at url, None if the provider has no score for it
+    fn score(&self, url: &str) -> Option<f32>;
+}
+
+impl<F> PageAuthority for F
+where
+    F: Fn(&str) -> Option<f32>,
+{
+    fn score(&self, url: &str) -> Option<f32> {
+        self(url)
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::search::RequestInfo;
+
+/// How many credits you can spend before they reset
+/// Build this from the RequestInfo of your latest response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CreditsBudget {
+    pub credits_remaining: usize,
+    pub reset_at: Option<SystemTime>, // None if credits_reset_at could not be parsed
+}
+
+impl CreditsBudget {
+
+    /// the budget as of the response request_info came from
+    pub fn from_request_info(request_info: &RequestInfo) -> Self {
+        CreditsBudget {
+            credits_remaining: request_info.credits_remaining,
+            reset_at: parse_timestamp(&request_info.credits_reset_at),
+        }
+    }
+
+    /// how long until credits reset. Zero if the reset time has already passed or is unknown
+    pub fn time_until_reset(&self, now: SystemTime) -> Duration {
+        match self.reset_at {
+            Some(reset_at) => reset_at.duration_since(now).unwrap_or(Duration::ZERO),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// how many searches per hour you can run until the reset without running out of credits
+    /// Within the last hour before the reset (or after it) this is simply the credits remaining
+    pub fn sustainable_rate_per_hour(&self, now: SystemTime) -> f64 {
+        let hours = self.time_until_reset(now).as_secs_f64() / 3600.0;
+        self.credits_remaining as f64 / hours.max(1.0)
+    }
+
+    /// how long to leave between searches so they keep to sustainable_rate_per_hour until the reset
+    /// Never longer than the time until the reset, and zero once the reset has passed (or is unknown), as credits have been renewed
+    pub fn spacing(&self, now: SystemTime) -> Duration {
+        let until_reset = self.time_until_reset(now);
+        if until_reset.is_zero() || self.credits_remaining == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(3600.0 / self.sustainable_rate_per_hour(now)).min(until_reset)
+    }
+
+    /// true if there are enough credits left for n more searches
+    pub fn can_afford(&self, n: usize) -> bool {
+        n <= self.credits_remaining
+    }
+}
+
+/// parse a UTC timestamp like "2021-07-31T01:00:37.000Z"
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let timestamp = timestamp.trim().strip_suffix('Z')?;
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i64>());
+    let (year, month, day) = (date_parts.next()?.ok()?, date_parts.next()?.ok()?, date_parts.next()?.ok()?);
+    let time = time.split('.').next()?;
+    let mut time_parts = time.splitn(3, ':').map(|part| part.parse::<u64>());
+    let (hour, minute, second) = (time_parts.next()?.ok()?, time_parts.next()?.ok()?, time_parts.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hour * 3600 + minute * 60 + second))
+}
+
+/// the number of days from 1970-01-01 to the given date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12; // March is 0
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(credits_remaining: usize, reset_at: &str) -> CreditsBudget {
+        CreditsBudget::from_request_info(&RequestInfo {
+            success: true,
+            credits_used: 0,
+            credits_used_this_request: 1,
+            credits_remaining,
+            credits_reset_at: reset_at.to_string(),
+        })
+    }
+
+    fn at(timestamp: &str) -> SystemTime {
+        parse_timestamp(timestamp).unwrap()
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(at("1970-01-01T00:00:00.000Z"), UNIX_EPOCH);
+        assert_eq!(at("2021-07-31T01:00:37.000Z"), UNIX_EPOCH + Duration::from_secs(1_627_693_237));
+        assert_eq!(at("2024-02-29T12:00:00Z"), UNIX_EPOCH + Duration::from_secs(1_709_208_000));
+        assert_eq!(parse_timestamp("next tuesday"), None);
+        assert_eq!(parse_timestamp("2021-13-01T00:00:00.000Z"), None);
+    }
+
+    #[test]
+    fn rates_around_the_reset() {
+        let budget = budget(1000, "2021-07-31T01:00:00.000Z");
+        let now = at("2021-07-30T15:00:00.000Z");
+        assert_eq!(budget.time_until_reset(now), Duration::from_secs(10 * 3600));
+        assert_eq!(budget.sustainable_rate_per_hour(now), 100.0);
+
+        // within the last hour the whole balance can be spent
+        let now = at("2021-07-31T00:30:00.000Z");
+        assert_eq!(budget.time_until_reset(now), Duration::from_secs(1800));
+        assert_eq!(budget.sustainable_rate_per_hour(now), 1000.0);
+
+        // exactly at and after the reset
+        assert_eq!(budget.time_until_reset(at("2021-07-31T01:00:00.000Z")), Duration::ZERO);
+        let later = at("2021-08-02T00:00:00.000Z");
+        assert_eq!(budget.time_until_reset(later), Duration::ZERO);
+        assert_eq!(budget.sustainable_rate_per_hour(later), 1000.0);
+
+        assert_eq!(budget.spacing(at("2021-07-30T15:00:00.000Z")), Duration::from_secs(36));
+        // 1000 an hour would run past the reset, so the wait stops at the reset
+        assert_eq!(budget.spacing(at("2021-07-31T00:59:59.000Z")), Duration::from_secs(1));
+        assert_eq!(budget.spacing(later), Duration::ZERO);
+
+        assert!(budget.can_afford(1000));
+        assert!(!budget.can_afford(1001));
+    }
+
+    #[test]
+    fn unparseable_reset() {
+        let budget = budget(50, "soon");
+        assert_eq!(budget.reset_at, None);
+        assert_eq!(budget.time_until_reset(SystemTime::now()), Duration::ZERO);
+        assert_eq!(budget.sustainable_rate_per_hour(SystemTime::now()), 50.0);
+        assert_eq!(budget.spacing(SystemTime::now()), Duration::ZERO);
+    }
+}
@@ -0,0 +1,1394 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::pin;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use futures_util::future::{self, Either, FutureExt};
+use futures_util::stream::{self, FuturesUnordered, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use tokio::time::Instant;
+use url::Url;
+use crate::account::AccountResp;
+use crate::budget::CreditsBudget;
+use crate::dialect::ResponseDialect;
+use crate::error::{Error, RequestContext};
+use crate::keys::KeyPool;
+use crate::market::MarketKey;
+use crate::locations::{LocReqConfig, Location, LocationResp};
+use crate::middleware::{ApiRequest, ApiResponse, Endpoint, Middleware};
+use crate::query;
+use crate::vertical;
+use crate::search::{AsyncJobResp, DuplicateFilter, Params, Resp, SearchOutcome, SearchType};
+
+/// the default limit on request URL length, a little under what most servers accept
+pub const DEFAULT_MAX_URL_LENGTH: usize = 8 * 1024;
+
+/// the endpoint for account details, which does not use any credits
+const ACCOUNT_URL: &str = "https://api.scaleserp.com/account";
+
+/// how many searches search_many_locations runs at once
+pub const LOCATION_CONCURRENCY: usize = 4;
+
+/// called after each search with its tag ("" if untagged), the credits it used and its query
+type CreditsCallback = Box<dyn Fn(&str, usize, &str) + Send + Sync>;
+
+/// A reusable client for calling ScaleSERP
+/// Reusing one client across many searches keeps connections to the API open between requests
+pub struct ScaleSerpClient {
+    http: reqwest::Client,
+    api_key: String,
+    pacer: Pacer,
+    max_url_length: usize,
+    max_query_length: Option<usize>,
+    trim_query: bool,
+    middleware: Vec<Box<dyn Middleware>>,
+    location_fallback: Option<LocationFallbackPolicy>,
+    dialect: ResponseDialect,
+    key_pool: Option<KeyPool>,
+    credits_by_tag: Mutex<HashMap<String, usize>>,
+    on_credits: Option<CreditsCallback>,
+}
+
+impl ScaleSerpClient {
+
+    /// start building a new client
+    pub fn builder() -> ScaleSerpClientBuilder {
+        ScaleSerpClientBuilder::new()
+    }
+
+    /// the API key used for calls that are not made from Params
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+
+    /// the total time searches have spent waiting for min_interval so far
+    pub fn total_wait(&self) -> Duration {
+        self.pacer.total_wait()
+    }
+
+    /// check that the API is reachable and accepts the API key (or the next key of the key pool)
+    /// This calls the account endpoint, which does not use any credits
+    pub async fn check_connectivity(&self) -> Result<(), Error> {
+        let url = self.account_url()?;
+        let response = self.execute(Endpoint::Account, &url).await
+            .map_err(|e| match e {
+                Error::Http { source, .. } => Error::ApiUnavailable(source.to_string()),
+                other => other,
+            })?;
+        connectivity_result(reqwest::StatusCode::from_u16(response.status).unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR))
+    }
+
+    /// details of the account the API key belongs to, including its credits
+    /// With a key pool this is the account of the next key in the pool.
+    /// This calls the account endpoint, which does not use any credits
+    pub async fn account(&self) -> Result<AccountResp, Error> {
+        let url = self.account_url()?;
+        self.get_json(Endpoint::Account, &url).await
+            .map_err(|e| match e {
+                Error::Api { status: 401 | 403, .. } => Error::InvalidApiKey,
+                other => other,
+            })
+    }
+
+    /// how many credits are left, without running a search
+    pub async fn get_credits_remaining(&self) -> Result<usize, Error> {
+        Ok(self.account().await?.account_info.credits_remaining)
+    }
+
+    /// look up locations to search from
+    pub async fn locations(&self, config: &LocReqConfig) -> Result<LocationResp, Error> {
+        self.get_json(Endpoint::Locations, &config.to_url()).await
+    }
+
+    /// perform a search
+    /// Errors carry the query and location they came from
+    pub async fn search(&self, params: &Params) -> Result<Resp, Error> {
+        Ok(self.search_with_fallback(params).await?.resp)
+    }
+
+    /// perform a search, retrying with broader locations if the client has a LocationFallbackPolicy
+    /// The result records which location was finally used
+    pub async fn search_with_fallback(&self, params: &Params) -> Result<FallbackSearch, Error> {
+        let (policy, location) = match (&self.location_fallback, &params.location) {
+            (Some(policy), Some(location)) => (policy, location),
+            _ => {
+                let resp = self.search_once(params).await?;
+                return Ok(FallbackSearch { resp, location: params.location.clone(), broadened: 0 });
+            },
+        };
+        let mut locations = vec![location.clone()];
+        locations.extend(broader_locations(location).into_iter().take(policy.max_retries));
+        let mut params = params.clone();
+        let last = locations.len() - 1;
+        for (broadened, location) in locations.into_iter().enumerate() {
+            params.location = Some(location);
+            match self.search_once(&params).await {
+                Ok(resp) if resp.location_fell_back() && broadened < last => continue,
+                Ok(resp) => return Ok(FallbackSearch { resp, location: params.location, broadened }),
+                Err(e) if is_location_error(&e) && broadened < last => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("the last location always returns")
+    }
+
+    /// perform a search that may be skipped because of params.skip_on_incident
+    /// A skipped search is SearchOutcome::SkippedIncident rather than an error. search returns Error::SkippedIncident instead
+    pub async fn search_outcome(&self, params: &Params) -> Result<SearchOutcome, Error> {
+        let params = self.checked_params(params)?;
+        let params = params.as_ref();
+        self.pacer.wait_for_turn().await;
+        self.send_search(params).await
+            .map_err(|e| e.with_context(RequestContext::from(params)))
+    }
+
+    /// the dialect responses are parsed with
+    pub fn dialect(&self) -> ResponseDialect {
+        self.dialect
+    }
+
+    /// every parameter a search for params sends: those of params and then the dialect's defaults that params does not set
+    /// With a key pool the api_key is replaced by the key picked for each search
+    pub fn effective_pairs(&self, params: &Params) -> Vec<(&'static str, String)> {
+        let mut pairs = params.effective_pairs();
+        for (key, value) in self.dialect.default_params() {
+            if !pairs.iter().any(|(set, _)| set == key) {
+                pairs.push((key, value.to_string()));
+            }
+        }
+        pairs
+    }
+
+    /// a listing of the request a search for params sends, one parameter per line with the API key masked, see Params's Display
+    pub fn describe_request(&self, params: &Params) -> String {
+        let mut listing = String::new();
+        query::write_listing(&mut listing, params.endpoint(), &self.effective_pairs(params))
+            .expect("writing to a String does not fail");
+        listing
+    }
+
+    /// the credits searches have used so far, summed by the tag of their Params. Untagged searches are counted under ""
+    pub fn credits_by_tag(&self) -> HashMap<String, usize> {
+        self.credits_by_tag.lock().unwrap().clone()
+    }
+
+    /// set every credit counter back to zero, returning what they held
+    pub fn reset_credits_by_tag(&self) -> HashMap<String, usize> {
+        std::mem::take(&mut *self.credits_by_tag.lock().unwrap())
+    }
+
+    /// the key pool searches are spread across, if the client has one
+    pub fn key_pool(&self) -> Option<&KeyPool> {
+        self.key_pool.as_ref()
+    }
+
+    /// perform a search, and again with DuplicateFilter::Disabled if Google left out very similar results
+    /// Use this to see the real total when auditing duplicate content. The second search uses another credit
+    pub async fn search_unfiltered(&self, params: &Params) -> Result<UnfilteredSearch, Error> {
+        let filtered = self.search(params).await?;
+        if !filtered.has_omitted_results() {
+            return Ok(UnfilteredSearch { filtered, unfiltered: None });
+        }
+        let unfiltered = self.search(&params.clone().with_filter(DuplicateFilter::Disabled)).await?;
+        Ok(UnfilteredSearch { filtered, unfiltered: Some(unfiltered) })
+    }
+
+    /// perform a search, also returning the rate limit headers of the response
+    /// Unlike search this never retries with broader locations
+    pub async fn search_with_rate_limit(&self, params: &Params) -> Result<(Resp, RateLimitInfo), Error> {
+        let (resp, stats) = self.search_with_stats(params).await?;
+        Ok((resp, stats.rate_limit))
+    }
+
+    /// perform a search, also returning how it was fetched, i.e. the dialect to archive it with (see archive::save_with_dialect)
+    /// Unlike search this never retries with broader locations
+    pub async fn search_with_stats(&self, params: &Params) -> Result<(Resp, FetchStats), Error> {
+        let params = self.checked_params(params)?;
+        let params = params.as_ref();
+        self.pacer.wait_for_turn().await;
+        let (outcome, stats) = self.send_search_with_stats(params).await
+            .map_err(|e| e.with_context(RequestContext::from(params)))?;
+        match outcome {
+            SearchOutcome::Completed(resp) => Ok((*resp, stats)),
+            SearchOutcome::SkippedIncident(skip) => Err(Error::SkippedIncident(skip.message)),
+        }
+    }
+
+    /// run a news search for q from location
+    pub async fn search_news(&self, q: &str, location: &str) -> Result<Resp, Error> {
+        self.search_vertical(SearchType::News, q, location).await
+    }
+
+    /// run an image search for q from location
+    pub async fn search_images(&self, q: &str, location: &str) -> Result<Resp, Error> {
+        self.search_vertical(SearchType::Images, q, location).await
+    }
+
+    /// run a shopping search for q from location
+    pub async fn search_shopping(&self, q: &str, location: &str) -> Result<Resp, Error> {
+        self.search_vertical(SearchType::Shopping, q, location).await
+    }
+
+    /// run a video search for q from location
+    pub async fn search_videos(&self, q: &str, location: &str) -> Result<Resp, Error> {
+        self.search_vertical(SearchType::Videos, q, location).await
+    }
+
+    /// run a places search for q from location
+    pub async fn search_places(&self, q: &str, location: &str) -> Result<Resp, Error> {
+        self.search_vertical(SearchType::Places, q, location).await
+    }
+
+    async fn search_vertical(&self, search_type: SearchType, q: &str, location: &str) -> Result<Resp, Error> {
+        let params = Params::new(&self.api_key, q, location).with_search_type(search_type);
+        self.search(&params).await
+    }
+
+    /// run many searches, at most concurrency at a time, returning the results in the same order as params
+    pub async fn search_many(&self, params: &[Params], concurrency: usize) -> Vec<Result<Resp, Error>> {
+        let options = SearchManyOptions { concurrency, grace_period: Duration::ZERO, budget: None };
+        let outcome = self.search_many_until(params, options, future::pending::<()>()).await;
+        let mut completed = outcome.completed;
+        completed.sort_by_key(|(i, _)| *i);
+        completed.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// run many searches like search_many, grouping the results by the market each was sent to
+    /// Within a market the results keep the order of their params
+    pub async fn search_many_by_market(&self, params: &[Params], concurrency: usize) -> HashMap<MarketKey, Vec<Result<Resp, Error>>> {
+        let results = self.search_many(params, concurrency).await;
+        let mut by_market: HashMap<MarketKey, Vec<Result<Resp, Error>>> = HashMap::new();
+        for (params, result) in params.iter().zip(results) {
+            by_market.entry(MarketKey::from(params)).or_default().push(result);
+        }
+        by_market
+    }
+
+    /// search for q from each of locations, at most LOCATION_CONCURRENCY at a time
+    /// The pairs are yielded as the searches complete, so not in the order of locations
+    pub fn search_many_locations<'a>(&'a self, q: &'a str, locations: &'a [Location]) -> impl Stream<Item = (Location, Result<Resp, Error>)> + 'a {
+        stream::iter(locations)
+            .map(move |location| async move {
+                let params = Params::new(&self.api_key, q, &location.full_name);
+                (location.clone(), self.search(&params).await)
+            })
+            .buffer_unordered(LOCATION_CONCURRENCY)
+    }
+
+    /// run many searches until cancel completes, i.e. on a shutdown signal
+    /// Once cancelled no new searches start. Searches already running get options.grace_period to finish,
+    /// and the outcome lists which params were never attempted (or abandoned) so they can be retried on the next run.
+    /// With options.budget, searches also stop starting once the budget can not afford another one: see SearchManyOutcome::over_budget.
+    /// Each running search reserves one credit, so searches that cost more can overspend by up to concurrency searches.
+    /// Until the budget's reset_at, starts are also spaced out so spending keeps to its sustainable_rate_per_hour (see CreditsBudget::spacing)
+    pub async fn search_many_until<F>(&self, params: &[Params], options: SearchManyOptions, cancel: F) -> SearchManyOutcome
+    where
+        F: Future<Output = ()>,
+    {
+        let mut cancel = pin!(cancel.fuse());
+        let mut in_flight = FuturesUnordered::new();
+        let mut started = HashSet::new();
+        let mut completed = Vec::new();
+        let mut next = 0;
+        let mut cancelled = false;
+        let mut budget = options.budget;
+        // the budget's reset is wall clock time, read through tokio's clock so it follows paused time in tests
+        let (started_at, started_instant) = (SystemTime::now(), Instant::now());
+        let mut paced_until: Option<Instant> = None;
+        loop {
+            if !cancelled && cancel.as_mut().now_or_never().is_some() {
+                cancelled = true;
+            }
+            while !cancelled && in_flight.len() < options.concurrency.max(1) && next < params.len() {
+                if let Some(budget) = &mut budget {
+                    if !budget.can_afford(1) || paced_until.is_some_and(|at| at > Instant::now()) {
+                        break;
+                    }
+                    let spacing = budget.spacing(started_at + started_instant.elapsed());
+                    paced_until = Some(Instant::now() + spacing);
+                    // reserve a credit until the response says what the search cost
+                    budget.credits_remaining -= 1;
+                }
+                let i = next;
+                in_flight.push(async move { (i, self.search(&params[i]).await) });
+                started.insert(i);
+                next += 1;
+            }
+            let pacing = !cancelled && next < params.len()
+                && budget.is_some_and(|budget| budget.can_afford(1))
+                && paced_until.is_some_and(|at| at > Instant::now());
+            if cancelled || (in_flight.is_empty() && !pacing) {
+                break;
+            }
+            let completion = async {
+                if in_flight.is_empty() { future::pending().await } else { in_flight.next().await }
+            };
+            let paced = async move {
+                match paced_until {
+                    Some(at) if pacing => tokio::time::sleep_until(at).await,
+                    _ => future::pending().await,
+                }
+            };
+            match future::select(pin!(completion), future::select(cancel.as_mut(), pin!(paced))).await {
+                Either::Left((Some((i, result)), _)) => {
+                    started.remove(&i);
+                    settle_reservation(&mut budget, &result);
+                    completed.push((i, result));
+                },
+                Either::Left((None, _)) => break,
+                Either::Right((Either::Left(_), _)) => cancelled = true,
+                Either::Right((Either::Right(_), _)) => {}, // time for the next search
+            }
+        }
+        let deadline = Instant::now() + options.grace_period;
+        while !in_flight.is_empty() {
+            match tokio::time::timeout_at(deadline, in_flight.next()).await {
+                Ok(Some((i, result))) => {
+                    started.remove(&i);
+                    settle_reservation(&mut budget, &result);
+                    completed.push((i, result));
+                },
+                Ok(None) | Err(_) => break,
+            }
+        }
+        let mut abandoned: Vec<usize> = started.into_iter().collect();
+        abandoned.sort_unstable();
+        // without cancellation, searches only stop starting early when the budget runs out
+        let unstarted: Vec<usize> = (next..params.len()).collect();
+        let (not_attempted, over_budget) = if cancelled { (unstarted, Vec::new()) } else { (Vec::new(), unstarted) };
+        SearchManyOutcome { completed, not_attempted, abandoned, over_budget }
+    }
+
+    /// queue a search with the asynchronous API, returning the id to collect the result with
+    /// This always uses the asynchronous API, whatever params.async_mode is set to
+    pub async fn search_async(&self, params: &Params) -> Result<AsyncJobResp, Error> {
+        let params = self.checked_params(params)?.into_owned().with_async(true);
+        self.pacer.wait_for_turn().await;
+        self.get_json(Endpoint::Search, &params.to_url()).await
+            .map_err(|e| e.with_context(RequestContext::from(&params)))
+    }
+
+    /// the account endpoint with the next key from the key pool if there is one, and the client's api_key otherwise
+    fn account_url(&self) -> Result<String, Error> {
+        let api_key = match &self.key_pool {
+            Some(pool) => pool.acquire()?.key().to_string(),
+            None => self.api_key.clone(),
+        };
+        Ok(query::url_with_pairs(ACCOUNT_URL, &[("api_key", api_key)]))
+    }
+
+    /// params with the query trimmed (unless trim_query was turned off), or the error from Params::validate if they can not be sent
+    fn checked_params<'a>(&self, params: &'a Params) -> Result<Cow<'a, Params>, Error> {
+        let params = if self.trim_query && params.q.trim() != params.q {
+            let mut trimmed = params.clone();
+            trimmed.q = params.q.trim().to_string();
+            Cow::Owned(trimmed)
+        } else {
+            Cow::Borrowed(params)
+        };
+        params.validate()?;
+        let length = params.q.chars().count();
+        match self.max_query_length {
+            Some(limit) if length > limit => Err(Error::InvalidQuery(format!("the query is {} characters long, over the limit of {}", length, limit))),
+            _ => Ok(params),
+        }
+    }
+
+    async fn search_once(&self, params: &Params) -> Result<Resp, Error> {
+        match self.search_outcome(params).await? {
+            SearchOutcome::Completed(resp) => Ok(*resp),
+            SearchOutcome::SkippedIncident(skip) => Err(Error::SkippedIncident(skip.message)),
+        }
+    }
+
+    async fn send_search(&self, params: &Params) -> Result<SearchOutcome, Error> {
+        Ok(self.send_search_with_stats(params).await?.0)
+    }
+
+    /// send the search with the next key from the key pool if there is one, and params.api_key otherwise
+    async fn send_search_with_stats(&self, params: &Params) -> Result<(SearchOutcome, FetchStats), Error> {
+        let pool = match &self.key_pool {
+            Some(pool) => pool,
+            None => return self.send_search_with_key(params).await,
+        };
+        let lease = pool.acquire()?;
+        if !lease.wait.is_zero() {
+            tokio::time::sleep(lease.wait).await;
+        }
+        log::debug!("searching for q={:?} with key {:?}", params.q, lease.label);
+        let mut params = params.clone();
+        params.api_key = lease.key().to_string();
+        let result = self.send_search_with_key(&params).await;
+        match &result {
+            Ok((SearchOutcome::Completed(resp), _)) => pool.report_success(lease.index, &resp.request_info),
+            Ok((SearchOutcome::SkippedIncident(_), _)) => {},
+            Err(e) => pool.report_error(lease.index, e),
+        }
+        result.map(|(outcome, stats)| (outcome, FetchStats { key: Some(lease.label), ..stats }))
+    }
+
+    async fn send_search_with_key(&self, params: &Params) -> Result<(SearchOutcome, FetchStats), Error> {
+        let url = query::url_with_pairs(params.endpoint(), &self.effective_pairs(params));
+        let response = self.execute(Endpoint::Search, &url).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(Error::Api { status: response.status, message: response.body, context: None });
+        }
+        let rate_limit = RateLimitInfo::from_headers(&response.headers);
+        let value: serde_json::Value = serde_json::from_str(&response.body)?;
+        if let Some(mismatch) = vertical::check(&value, params.search_type) {
+            log::warn!("{} for q={:?}", mismatch, params.q);
+        }
+        let outcome = self.dialect.parse_value(value)?;
+        if let SearchOutcome::Completed(resp) = &outcome {
+            self.record_credits(params, resp.request_info.credits_used_this_request);
+            for warning in resp.warnings() {
+                log::warn!("{:?} for q={:?}", warning, params.q);
+            }
+        }
+        Ok((outcome, FetchStats { dialect: self.dialect, rate_limit, key: None }))
+    }
+
+    /// add credits to the counter for the tag of params and tell the on_credits callback
+    fn record_credits(&self, params: &Params, credits: usize) {
+        let tag = params.tag.as_deref().unwrap_or_default();
+        *self.credits_by_tag.lock().unwrap().entry(tag.to_string()).or_default() += credits;
+        if let Some(callback) = &self.on_credits {
+            callback(tag, credits, &params.q);
+        }
+    }
+
+    /// call url and deserialize the JSON it returns, treating error statuses as Error::Api
+    async fn get_json<T: DeserializeOwned>(&self, endpoint: Endpoint, url: &str) -> Result<T, Error> {
+        let response = self.execute(endpoint, url).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(Error::Api { status: response.status, message: response.body, context: None });
+        }
+        let value: T = serde_json::from_str(&response.body)?;
+        Ok(value)
+    }
+
+    /// send a GET request to url, running it through the middleware
+    async fn execute(&self, endpoint: Endpoint, url: &str) -> Result<ApiResponse, Error> {
+        let mut request = ApiRequest { endpoint, url: Url::parse(url)? };
+        let mut response = Ok(());
+        for middleware in &self.middleware {
+            response = middleware.before_request(&mut request);
+            if response.is_err() {
+                break;
+            }
+        }
+        let response = match response {
+            Ok(()) => self.send(&request).await,
+            Err(e) => Err(e),
+        };
+        for middleware in &self.middleware {
+            middleware.after_response(&request, &response);
+        }
+        response
+    }
+
+    async fn send(&self, request: &ApiRequest) -> Result<ApiResponse, Error> {
+        check_url_length(request.url.as_str(), self.max_url_length)?;
+        let response = self.http.get(request.url.clone()).send().await?;
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        Ok(ApiResponse { status, headers, body })
+    }
+}
+
+/// The rate limit ScaleSERP reported in the headers of a response
+/// Either field is None if its header was missing or could not be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RateLimitInfo {
+    pub remaining: Option<u32>, // from X-RateLimit-Remaining
+    pub reset_at: Option<u64>, // from X-RateLimit-Reset, in seconds since the Unix epoch
+}
+
+impl RateLimitInfo {
+
+    /// read the rate limit from response headers
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name)?.to_str().ok().map(|value| value.trim().to_string());
+        RateLimitInfo {
+            remaining: header("x-ratelimit-remaining").and_then(|value| value.parse().ok()),
+            reset_at: header("x-ratelimit-reset").and_then(|value| value.parse().ok()),
+        }
+    }
+}
+
+/// How one search was fetched, see ScaleSerpClient::search_with_stats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchStats {
+    pub dialect: ResponseDialect, // the dialect the response was parsed with
+    pub rate_limit: RateLimitInfo,
+    pub key: Option<String>, // the label of the key from the client's KeyPool that served the search, None without a pool
+}
+
+/// Settings for ScaleSerpClient::search_many_until
+#[derive(Debug, Clone, Copy)]
+pub struct SearchManyOptions {
+    pub concurrency: usize, // how many searches run at once
+    pub grace_period: Duration, // how long running searches get to finish after cancellation
+    pub budget: Option<CreditsBudget>, // pace searches to it and stop once it can not afford another, None for no limit
+}
+
+/// replace the credit reserved for a search with what it actually cost
+/// Skipped searches are free. Other errors keep the reservation, as it is not known whether they were charged
+fn settle_reservation(budget: &mut Option<CreditsBudget>, result: &Result<Resp, Error>) {
+    let Some(budget) = budget else { return };
+    match result {
+        Ok(resp) => budget.credits_remaining = (budget.credits_remaining + 1).saturating_sub(resp.request_info.credits_used_this_request),
+        Err(Error::SkippedIncident(_)) => budget.credits_remaining += 1,
+        Err(_) => {},
+    }
+}
+
+/// What happened to each of the params passed to search_many_until, by index
+#[derive(Debug)]
+pub struct SearchManyOutcome {
+    pub completed: Vec<(usize, Result<Resp, Error>)>, // in the order they completed
+    pub not_attempted: Vec<usize>, // never started because of cancellation
+    pub abandoned: Vec<usize>, // started, but did not finish within the grace period
+    pub over_budget: Vec<usize>, // never started because SearchManyOptions::budget ran out
+}
+
+impl SearchManyOutcome {
+
+    /// the indexes of the params ScaleSERP skipped because of a Google incident, to requeue later
+    pub fn skipped_incidents(&self) -> Vec<usize> {
+        let mut skipped: Vec<usize> = self.completed.iter()
+            .filter(|(_, result)| matches!(result, Err(Error::SkippedIncident(_))))
+            .map(|(i, _)| *i)
+            .collect();
+        skipped.sort_unstable();
+        skipped
+    }
+
+    /// how many searches ScaleSERP skipped because of a Google incident
+    pub fn skipped_incident_count(&self) -> usize {
+        self.completed.iter().filter(|(_, result)| matches!(result, Err(Error::SkippedIncident(_)))).count()
+    }
+}
+
+/// The responses from ScaleSerpClient::search_unfiltered
+#[derive(Debug)]
+pub struct UnfilteredSearch {
+    pub filtered: Resp, // the search as requested
+    pub unfiltered: Option<Resp>, // the search with DuplicateFilter::Disabled, None if nothing was omitted
+}
+
+/// The result of a search that may have been retried with broader locations
+#[derive(Debug)]
+pub struct FallbackSearch {
+    pub resp: Resp,
+    pub location: Option<String>, // the location that was finally searched from, None if the search was not geotargeted
+    pub broadened: usize, // how many levels broader than requested, 0 if the original location worked
+}
+
+/// Retry searches whose location was rejected or ignored with progressively broader locations,
+/// i.e. "Chicago,Illinois,United States" then "Illinois,United States" then "United States"
+#[derive(Debug, Clone, Copy)]
+pub struct LocationFallbackPolicy {
+    pub max_retries: usize, // each retry uses a credit
+}
+
+impl Default for LocationFallbackPolicy {
+    fn default() -> Self {
+        LocationFallbackPolicy { max_retries: 2 }
+    }
+}
+
+/// the locations to fall back to, broadest last
+/// i.e. ["Illinois,United States", "United States"] for "Chicago,Illinois,United States"
+pub fn broader_locations(location: &str) -> Vec<String> {
+    let parts: Vec<&str> = location.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()).collect();
+    (1..parts.len()).map(|start| parts[start..].join(",")).collect()
+}
+
+/// true if the API rejected the search because of its location
+fn is_location_error(error: &Error) -> bool {
+    match error {
+        Error::Api { status, message, .. } => (400..500).contains(status) && message.to_lowercase().contains("location"),
+        _ => false,
+    }
+}
+
+/// Spaces out request starts so that no two are closer together than min_interval
+/// Each caller reserves the next free start time before sleeping, so concurrent callers queue up in order
+struct Pacer {
+    min_interval: Option<Duration>,
+    state: Mutex<PacerState>,
+}
+
+struct PacerState {
+    last_start: Option<Instant>,
+    total_wait: Duration,
+}
+
+impl Pacer {
+
+    fn new(min_interval: Option<Duration>) -> Self {
+        Pacer {
+            min_interval,
+            state: Mutex::new(PacerState { last_start: None, total_wait: Duration::ZERO }),
+        }
+    }
+
+    /// wait until this request is allowed to start
+    async fn wait_for_turn(&self) {
+        let min_interval = match self.min_interval {
+            None => return,
+            Some(interval) => interval,
+        };
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let start = match state.last_start {
+                Some(last) => (last + min_interval).max(now),
+                None => now,
+            };
+            state.last_start = Some(start);
+            state.total_wait += start - now;
+            start - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn total_wait(&self) -> Duration {
+        self.state.lock().unwrap().total_wait
+    }
+}
+
+/// refuse URLs longer than limit before they are sent
+fn check_url_length(url: &str, limit: usize) -> Result<(), Error> {
+    if url.len() > limit {
+        return Err(Error::UrlTooLong { length: url.len(), limit });
+    }
+    Ok(())
+}
+
+/// interpret the status code returned by the account endpoint
+fn connectivity_result(status: reqwest::StatusCode) -> Result<(), Error> {
+    if status.is_success() {
+        Ok(())
+    } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        Err(Error::InvalidApiKey)
+    } else {
+        Err(Error::ApiUnavailable(format!("account endpoint returned {}", status)))
+    }
+}
+
+
+/// Configuration for a ScaleSerpClient
+pub struct ScaleSerpClientBuilder {
+    api_key: Option<String>,
+    reqwest_builder: Option<reqwest::ClientBuilder>,
+    min_interval: Option<Duration>,
+    max_url_length: usize,
+    max_query_length: Option<usize>,
+    trim_query: bool,
+    middleware: Vec<Box<dyn Middleware>>,
+    location_fallback: Option<LocationFallbackPolicy>,
+    dialect: ResponseDialect,
+    key_pool: Option<KeyPool>,
+    on_credits: Option<CreditsCallback>,
+    local_address: Option<String>,
+    prefer_ipv4: bool,
+    dns_overrides: Vec<(String, String)>,
+}
+
+impl ScaleSerpClientBuilder {
+
+    /// create a new builder with default settings
+    pub fn new() -> Self {
+        ScaleSerpClientBuilder {
+            api_key: None,
+            reqwest_builder: None,
+            min_interval: None,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            max_query_length: None,
+            trim_query: true,
+            middleware: Vec::new(),
+            location_fallback: None,
+            dialect: ResponseDialect::default(),
+            key_pool: None,
+            on_credits: None,
+            local_address: None,
+            prefer_ipv4: false,
+            dns_overrides: Vec::new(),
+        }
+    }
+
+    /// set the API key. If this is not called the environment variable SCALE_SERP_KEY is used
+    pub fn api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// build the underlying HTTP client from builder
+    /// Use this to set TLS options, timeouts, a user agent, pool sizes or anything else reqwest supports
+    pub fn with_reqwest_builder(mut self, builder: reqwest::ClientBuilder) -> Self {
+        self.reqwest_builder = Some(builder);
+        self
+    }
+
+    /// leave at least min_interval between the starts of consecutive searches
+    /// Use this to spread credit usage out over time. By default searches start as soon as they are called
+    pub fn min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = Some(min_interval);
+        self
+    }
+
+    /// refuse to send searches whose URL is longer than max_url_length (DEFAULT_MAX_URL_LENGTH by default)
+    pub fn max_url_length(mut self, max_url_length: usize) -> Self {
+        self.max_url_length = max_url_length;
+        self
+    }
+
+    /// refuse to send searches whose query is longer than max_query_length characters. There is no limit by default
+    pub fn max_query_length(mut self, max_query_length: usize) -> Self {
+        self.max_query_length = Some(max_query_length);
+        self
+    }
+
+    /// trim whitespace from around queries before sending them, true by default
+    /// Turn this off if leading or trailing whitespace is significant to your queries
+    pub fn trim_query(mut self, trim_query: bool) -> Self {
+        self.trim_query = trim_query;
+        self
+    }
+
+    /// add middleware that runs around every call the client makes, after any middleware added before it
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// retry searches with broader locations when their location is rejected or ignored
+    pub fn location_fallback(mut self, policy: LocationFallbackPolicy) -> Self {
+        self.location_fallback = Some(policy);
+        self
+    }
+
+    /// how to parse responses and which optional parameters to send, ResponseDialect::V1Lenient by default
+    pub fn dialect(mut self, dialect: ResponseDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// spread searches across the keys in pool instead of using the api_key of their Params
+    pub fn key_pool(mut self, pool: KeyPool) -> Self {
+        self.key_pool = Some(pool);
+        self
+    }
+
+    /// call callback after each search with its tag ("" if untagged), the credits it used and its query
+    /// Use this to push credit usage to your own metrics. It runs on the task that made the search, so keep it quick
+    pub fn on_credits(mut self, callback: impl Fn(&str, usize, &str) + Send + Sync + 'static) -> Self {
+        self.on_credits = Some(Box::new(callback));
+        self
+    }
+
+    /// send every request from this local IP address, i.e. "10.0.0.5"
+    pub fn local_address(mut self, address: &str) -> Self {
+        self.local_address = Some(address.to_string());
+        self
+    }
+
+    /// only connect over IPv4, i.e. when IPv6 routing is broken
+    pub fn prefer_ipv4(mut self, prefer_ipv4: bool) -> Self {
+        self.prefer_ipv4 = prefer_ipv4;
+        self
+    }
+
+    /// connect to the IP address instead of looking host up in DNS, i.e. to pin api.scaleserp.com during an incident
+    /// Connections still go to the port in the request URL
+    pub fn resolve(mut self, host: &str, address: &str) -> Self {
+        self.dns_overrides.push((host.to_string(), address.to_string()));
+        self
+    }
+
+    /// build the client
+    pub fn build(self) -> Result<ScaleSerpClient, Error> {
+        let api_key = match self.api_key {
+            Some(key) => key,
+            None => env::var("SCALE_SERP_KEY").unwrap_or_default(),
+        };
+        let connection = ConnectionOptions::parse(self.local_address.as_deref(), self.prefer_ipv4, &self.dns_overrides)?;
+        let http = connection.apply(self.reqwest_builder.unwrap_or_default()).build()?;
+        Ok(ScaleSerpClient {
+            http,
+            api_key,
+            pacer: Pacer::new(self.min_interval),
+            max_url_length: self.max_url_length,
+            max_query_length: self.max_query_length,
+            trim_query: self.trim_query,
+            middleware: self.middleware,
+            location_fallback: self.location_fallback,
+            dialect: self.dialect,
+            key_pool: self.key_pool,
+            credits_by_tag: Mutex::new(HashMap::new()),
+            on_credits: self.on_credits,
+        })
+    }
+}
+
+/// The connection settings of a ScaleSerpClientBuilder, checked before the client is built
+#[derive(Debug, PartialEq, Eq)]
+struct ConnectionOptions {
+    local_address: Option<IpAddr>,
+    dns_overrides: Vec<(String, SocketAddr)>,
+}
+
+impl ConnectionOptions {
+
+    fn parse(local_address: Option<&str>, prefer_ipv4: bool, dns_overrides: &[(String, String)]) -> Result<Self, Error> {
+        let local_address = match local_address {
+            Some(address) => Some(address.trim().parse::<IpAddr>()
+                .map_err(|_| Error::InvalidConfig(format!("local_address {:?} is not an IP address", address)))?),
+            None => None,
+        };
+        let local_address = match (local_address, prefer_ipv4) {
+            (Some(IpAddr::V6(address)), true) => {
+                return Err(Error::InvalidConfig(format!("local_address {} is IPv6, but prefer_ipv4 is set", address)));
+            },
+            // binding to the unspecified IPv4 address keeps every connection on IPv4
+            (None, true) => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            (address, _) => address,
+        };
+        let dns_overrides = dns_overrides.iter()
+            .map(|(host, address)| {
+                let address = address.trim();
+                let ip = address.parse::<IpAddr>()
+                    .map_err(|_| Error::InvalidConfig(format!("resolve address {:?} for {} is not an IP address", address, host)))?;
+                // reqwest ignores the port of an override
+                Ok((host.clone(), SocketAddr::new(ip, 0)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(ConnectionOptions { local_address, dns_overrides })
+    }
+
+    fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(address) = self.local_address {
+            builder = builder.local_address(address);
+        }
+        for (host, address) in &self.dns_overrides {
+            builder = builder.resolve(host, *address);
+        }
+        builder
+    }
+}
+
+impl Default for ScaleSerpClientBuilder {
+    fn default() -> Self {
+        ScaleSerpClientBuilder::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn build_with_reqwest_builder() {
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .with_reqwest_builder(reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .user_agent("scale_serp tests"))
+            .build()
+            .unwrap();
+        assert_eq!(client.api_key(), "demo");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pacer_spaces_out_starts() {
+        let pacer = Pacer::new(Some(Duration::from_secs(10)));
+        let begin = Instant::now();
+        pacer.wait_for_turn().await; // the first request never waits
+        assert_eq!(begin.elapsed(), Duration::ZERO);
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        pacer.wait_for_turn().await;
+        assert_eq!(begin.elapsed(), Duration::from_secs(10));
+        tokio::time::sleep(Duration::from_secs(15)).await;
+        pacer.wait_for_turn().await; // already past the interval
+        assert_eq!(begin.elapsed(), Duration::from_secs(25));
+        assert_eq!(pacer.total_wait(), Duration::from_secs(6));
+
+        let unpaced = Pacer::new(None);
+        unpaced.wait_for_turn().await;
+        assert_eq!(unpaced.total_wait(), Duration::ZERO);
+    }
+
+    #[test]
+    fn url_length_limit() {
+        let params = Params::new_env_usa(&"long tail question ".repeat(500));
+        let url = params.to_url();
+        assert!(check_url_length(&url, DEFAULT_MAX_URL_LENGTH).is_err());
+        match check_url_length(&url, 100) {
+            Err(Error::UrlTooLong { length, limit }) => assert_eq!((length, limit), (url.len(), 100)),
+            _ => panic!("expected UrlTooLong"),
+        }
+        assert!(check_url_length(&Params::new_env_usa("external ssd").to_url(), DEFAULT_MAX_URL_LENGTH).is_ok());
+    }
+
+    #[test]
+    fn location_fallback() {
+        assert_eq!(broader_locations("Chicago,Illinois,United States"), vec!["Illinois,United States", "United States"]);
+        assert_eq!(broader_locations("United States"), Vec::<String>::new());
+        assert_eq!(broader_locations("Chicago, Illinois ,"), vec!["Illinois"]);
+
+        let rejected = Error::Api { status: 400, message: "Invalid location parameter".to_string(), context: None };
+        assert!(is_location_error(&rejected));
+        let out_of_credits = Error::Api { status: 402, message: "Out of credits".to_string(), context: None };
+        assert!(!is_location_error(&out_of_credits));
+        assert!(!is_location_error(&Error::InvalidApiKey));
+    }
+
+    /// blocks every request (so nothing reaches the network) and fires a cancel signal on the nth request
+    struct CancelOnNth {
+        n: usize,
+        seen: Mutex<usize>,
+        cancel: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    }
+
+    impl Middleware for CancelOnNth {
+        fn before_request(&self, _request: &mut ApiRequest) -> Result<(), Error> {
+            let mut seen = self.seen.lock().unwrap();
+            *seen += 1;
+            if *seen == self.n {
+                if let Some(cancel) = self.cancel.lock().unwrap().take() {
+                    cancel.send(()).unwrap();
+                }
+            }
+            Err(Error::Blocked(format!("request {}", *seen)))
+        }
+    }
+
+    fn cancelling_client(n: usize) -> (ScaleSerpClient, tokio::sync::oneshot::Receiver<()>) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .middleware(CancelOnNth { n, seen: Mutex::new(0), cancel: Mutex::new(Some(sender)) })
+            .build()
+            .unwrap();
+        (client, receiver)
+    }
+
+    #[tokio::test]
+    async fn search_many_in_order_and_cancellation() {
+        let params: Vec<Params> = ["a", "b", "c", "d"].iter().map(|q| Params::new("demo", q, "United States")).collect();
+
+        let (client, _receiver) = cancelling_client(usize::MAX);
+        let results = client.search_many(&params, 2).await;
+        let messages: Vec<String> = results.into_iter().map(|r| r.unwrap_err().to_string()).collect();
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().all(|m| m.starts_with("request blocked: request ")));
+
+        // the second request fires the signal, so the third and fourth are never attempted
+        let (client, receiver) = cancelling_client(2);
+        let options = SearchManyOptions { concurrency: 1, grace_period: Duration::from_secs(1), budget: None };
+        let outcome = client.search_many_until(&params, options, async { receiver.await.unwrap(); }).await;
+        let completed: Vec<usize> = outcome.completed.iter().map(|(i, _)| *i).collect();
+        assert_eq!(completed, vec![0, 1]);
+        assert_eq!(outcome.not_attempted, vec![2, 3]);
+        assert!(outcome.abandoned.is_empty());
+
+        // already cancelled before starting
+        let (client, _receiver) = cancelling_client(usize::MAX);
+        let outcome = client.search_many_until(&params, options, async {}).await;
+        assert!(outcome.completed.is_empty());
+        assert_eq!(outcome.not_attempted, vec![0, 1, 2, 3]);
+    }
+
+    /// blocks every request after recording its URL
+    struct RecordUrl(Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for RecordUrl {
+        fn before_request(&self, request: &mut ApiRequest) -> Result<(), Error> {
+            self.0.lock().unwrap().push(request.redacted_url());
+            Err(Error::Blocked("recorded".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn dialect_default_params() {
+        let urls = Arc::new(Mutex::new(Vec::new()));
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .dialect(ResponseDialect::Latest)
+            .middleware(RecordUrl(urls.clone()))
+            .build()
+            .unwrap();
+        assert_eq!(client.dialect(), ResponseDialect::Latest);
+        let _ = client.search(&Params::new("demo", "ssd", "United States")).await;
+        assert_eq!(*urls.lock().unwrap(), vec!["https://api.scaleserp.com/search?api_key=***&location=United%20States&q=ssd&hide_base64_images=true"]);
+    }
+
+    #[tokio::test]
+    async fn query_validation() {
+        let urls = Arc::new(Mutex::new(Vec::new()));
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .max_query_length(10)
+            .middleware(RecordUrl(urls.clone()))
+            .build()
+            .unwrap();
+        let params: Vec<Params> = ["", "  ssd  ", " \t ", "external ssd drive"].iter()
+            .map(|q| Params::new("demo", q, "United States"))
+            .collect();
+        let results = client.search_many(&params, 2).await;
+        let messages: Vec<String> = results.into_iter().map(|r| r.unwrap_err().to_string()).collect();
+        assert_eq!(messages, vec![
+            "invalid query: the query is empty",
+            "request blocked: recorded",
+            "invalid query: the query is empty",
+            "invalid query: the query is 18 characters long, over the limit of 10",
+        ]);
+        // only the valid query was sent, trimmed
+        assert_eq!(*urls.lock().unwrap(), vec!["https://api.scaleserp.com/search?api_key=***&location=United%20States&q=ssd"]);
+
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .trim_query(false)
+            .middleware(RecordUrl(urls.clone()))
+            .build()
+            .unwrap();
+        let _ = client.search(&params[1]).await;
+        assert!(urls.lock().unwrap()[1].ends_with("&q=%20%20ssd%20%20"));
+        assert!(matches!(client.search_async(&params[0]).await, Err(Error::InvalidQuery(_))));
+    }
+
+    #[tokio::test]
+    async fn search_many_by_market() {
+        let (client, _receiver) = cancelling_client(usize::MAX);
+        let params = vec![
+            Params::new("demo", "a", "United States").with_gl("us"),
+            Params::new("demo", "b", "London,England,United Kingdom").with_gl("gb").with_google_domain("google.co.uk"),
+            Params::new("demo", "c", "United States").with_gl("US"),
+        ];
+        let by_market = client.search_many_by_market(&params, 2).await;
+        assert_eq!(by_market.len(), 2);
+        let us: Vec<String> = by_market[&MarketKey::from(&params[0])].iter().map(|r| r.as_ref().unwrap_err().to_string()).collect();
+        assert_eq!(us.len(), 2);
+        assert_eq!(by_market[&MarketKey::from(&params[1])].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_many_locations() {
+        let urls = Arc::new(Mutex::new(Vec::new()));
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .middleware(RecordUrl(urls.clone()))
+            .build()
+            .unwrap();
+        let locations: Vec<Location> = ["Boston,Massachusetts,United States", "Austin,Texas,United States", "United States"].iter()
+            .enumerate()
+            .map(|(i, full_name)| serde_json::from_value(serde_json::json!({
+                "id": i, "name": full_name, "type": "City", "full_name": full_name, "parent_id": 0,
+                "country_code": "US", "reach": 1000, "gps_coordinates": {"latitude": 0.0, "longitude": 0.0}
+            })).unwrap())
+            .collect();
+        let mut pairs: Vec<(Location, Result<Resp, Error>)> = client.search_many_locations("ssd", &locations).collect().await;
+        pairs.sort_by_key(|(location, _)| location.id);
+        let ids: Vec<i32> = pairs.iter().map(|(location, _)| location.id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert!(pairs.iter().all(|(_, result)| matches!(result, Err(Error::Blocked(_)))));
+        let mut urls = urls.lock().unwrap().clone();
+        urls.sort();
+        assert_eq!(urls, vec![
+            "https://api.scaleserp.com/search?api_key=***&location=Austin,Texas,United%20States&q=ssd",
+            "https://api.scaleserp.com/search?api_key=***&location=Boston,Massachusetts,United%20States&q=ssd",
+            "https://api.scaleserp.com/search?api_key=***&location=United%20States&q=ssd",
+        ]);
+    }
+
+    /// rejects requests made with "bad-key" as a 401, and blocks the rest after recording their URL
+    struct RejectBadKey(Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for RejectBadKey {
+        fn before_request(&self, request: &mut ApiRequest) -> Result<(), Error> {
+            if request.url.query_pairs().any(|(key, value)| key == "api_key" && value == "bad-key") {
+                return Err(Error::Api { status: 401, message: "invalid api_key".to_string(), context: None });
+            }
+            let key = request.url.query_pairs().find(|(key, _)| key == "api_key").unwrap().1.to_string();
+            self.0.lock().unwrap().push(key);
+            Err(Error::Blocked("recorded".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn key_pool() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let pool = KeyPool::new().with_key("first", "good-key-1").with_key("broken", "bad-key").with_key("second", "good-key-2");
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .key_pool(pool)
+            .middleware(RejectBadKey(keys.clone()))
+            .build()
+            .unwrap();
+        let params = Params::new("unused", "ssd", "United States");
+        let mut results = Vec::new();
+        for _ in 0..5 {
+            results.push(client.search(&params).await);
+        }
+        assert!(matches!(results[1], Err(Error::Api { status: 401, .. })));
+        // the rejected key is left out once it has failed
+        assert_eq!(*keys.lock().unwrap(), vec!["good-key-1", "good-key-2", "good-key-1", "good-key-2"]);
+        let stats = client.key_pool().unwrap().stats();
+        assert_eq!(stats[1].label, "broken");
+        assert!(stats[1].cooling_down && !stats[0].cooling_down);
+        assert_eq!(stats.iter().map(|key| key.requests).sum::<usize>(), 5);
+    }
+
+    #[tokio::test]
+    async fn connectivity_check_keys() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let client = ScaleSerpClient::builder().api_key("demo&key").middleware(RejectBadKey(keys.clone())).build().unwrap();
+        assert!(matches!(client.check_connectivity().await, Err(Error::Blocked(_))));
+        assert!(matches!(client.account().await, Err(Error::Blocked(_))));
+        let pool = KeyPool::new().with_key("first", "pool key/1").with_key("broken", "bad-key");
+        let client = ScaleSerpClient::builder().key_pool(pool).middleware(RejectBadKey(keys.clone())).build().unwrap();
+        let _ = client.check_connectivity().await;
+        assert!(matches!(client.check_connectivity().await, Err(Error::Api { status: 401, .. })));
+        // the keys arrive whole, so they were encoded
+        assert_eq!(*keys.lock().unwrap(), vec!["demo&key", "demo&key", "pool key/1"]);
+    }
+
+    /// answers every search on 127.0.0.1 with the fixture, charging as many credits as the query has characters
+    async fn serve_fixture() -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let read = stream.read(&mut buffer).await.unwrap();
+                        if read == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buffer[..read]);
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split_whitespace().nth(1).unwrap();
+                    let url = Url::parse(&format!("http://localhost{}", path)).unwrap();
+                    let q = url.query_pairs().find(|(name, _)| name == "q").unwrap().1.into_owned();
+                    let mut json: serde_json::Value = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+                    json["request_info"]["credits_used_this_request"] = q.chars().count().into();
+                    let body = json.to_string();
+                    let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        port
+    }
+
+    /// sends every request to the mock server on port instead of ScaleSERP
+    struct ToLocalhost(u16);
+
+    impl Middleware for ToLocalhost {
+        fn before_request(&self, request: &mut ApiRequest) -> Result<(), Error> {
+            request.url.set_scheme("http").unwrap();
+            request.url.set_host(Some("127.0.0.1")).unwrap();
+            request.url.set_port(Some(self.0)).unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn search_many_stops_at_the_budget() {
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .middleware(ToLocalhost(serve_fixture().await))
+            .build()
+            .unwrap();
+        // the mock charges one credit per character of the query
+        let params: Vec<Params> = ["a", "b", "cc", "d", "e", "f"].iter().map(|q| Params::new("demo", q, "")).collect();
+        let budget = CreditsBudget { credits_remaining: 4, reset_at: None };
+        let options = SearchManyOptions { concurrency: 1, grace_period: Duration::ZERO, budget: Some(budget) };
+        let outcome = client.search_many_until(&params, options, future::pending::<()>()).await;
+        let mut completed: Vec<usize> = outcome.completed.iter().map(|(i, _)| *i).collect();
+        completed.sort_unstable();
+        assert_eq!(completed, vec![0, 1, 2]);
+        assert_eq!(outcome.over_budget, vec![3, 4, 5]);
+        assert!(outcome.not_attempted.is_empty() && outcome.abandoned.is_empty());
+        assert_eq!(client.credits_by_tag()[""], 4);
+
+        let options = SearchManyOptions { concurrency: 2, budget: None, ..options };
+        let outcome = client.search_many_until(&params, options, future::pending::<()>()).await;
+        assert_eq!(outcome.completed.len(), 6);
+        assert!(outcome.over_budget.is_empty());
+    }
+
+    /// blocks every request after recording when it started
+    struct RecordStart(Arc<Mutex<Vec<Instant>>>);
+
+    impl Middleware for RecordStart {
+        fn before_request(&self, _request: &mut ApiRequest) -> Result<(), Error> {
+            self.0.lock().unwrap().push(Instant::now());
+            Err(Error::Blocked("recorded".to_string()))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn search_many_paces_to_the_budget() {
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let client = ScaleSerpClient::builder().api_key("demo").middleware(RecordStart(starts.clone())).build().unwrap();
+        let params: Vec<Params> = (0..10).map(|i| Params::new("demo", &i.to_string(), "")).collect();
+        // 8 credits for the 2 hours until the reset is 4 an hour. Blocked searches keep their reserved credit
+        let budget = CreditsBudget { credits_remaining: 8, reset_at: Some(SystemTime::now() + Duration::from_secs(2 * 3600)) };
+        let options = SearchManyOptions { concurrency: 2, grace_period: Duration::ZERO, budget: Some(budget) };
+        let begin = Instant::now();
+        let outcome = client.search_many_until(&params, options, future::pending::<()>()).await;
+        let minutes: Vec<u64> = starts.lock().unwrap().iter().map(|at| ((*at - begin).as_secs_f64() / 60.0).round() as u64).collect();
+        // within the last hour the rate follows the credits left, and the last wait is cut short at the reset (120)
+        assert_eq!(minutes, vec![0, 15, 30, 45, 60, 75, 95, 120]);
+        assert_eq!(outcome.over_budget, vec![8, 9]);
+        assert_eq!(outcome.completed.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn fetch_stats() {
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .dialect(ResponseDialect::V1Strict)
+            .middleware(ToLocalhost(serve_fixture().await))
+            .build()
+            .unwrap();
+        let (resp, stats) = client.search_with_stats(&Params::new("demo", "external ssd", "")).await.unwrap();
+        assert_eq!(resp.search_parameters.q, "external ssd");
+        assert_eq!(stats, FetchStats { dialect: ResponseDialect::V1Strict, rate_limit: RateLimitInfo::default(), key: None });
+
+        let pool = KeyPool::new().with_key("first", "key-1").with_key("second", "key-2");
+        let client = ScaleSerpClient::builder().key_pool(pool).middleware(ToLocalhost(serve_fixture().await)).build().unwrap();
+        let mut keys = Vec::new();
+        for _ in 0..3 {
+            keys.push(client.search_with_stats(&Params::new("unused", "ssd", "")).await.unwrap().1.key.unwrap());
+        }
+        assert_eq!(keys, vec!["first", "second", "first"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn credits_by_tag() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let callback_reported = reported.clone();
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .middleware(ToLocalhost(serve_fixture().await))
+            .on_credits(move |tag, credits, q| callback_reported.lock().unwrap().push((tag.to_string(), credits, q.to_string())))
+            .build()
+            .unwrap();
+        let params: Vec<Params> = (0..60).map(|i| {
+            let params = Params::new("demo", &"q".repeat(i % 5 + 1), "");
+            match i % 3 {
+                0 => params.with_tag("seo"),
+                1 => params.with_tag("ads"),
+                _ => params,
+            }
+        }).collect();
+        let expected = |tag: Option<&str>| params.iter().filter(|p| p.tag.as_deref() == tag).map(|p| p.q.len()).sum::<usize>();
+
+        let results = client.search_many(&params, 16).await;
+        assert!(results.iter().all(|result| result.is_ok()));
+        let totals = client.credits_by_tag();
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals["seo"], expected(Some("seo")));
+        assert_eq!(totals["ads"], expected(Some("ads")));
+        assert_eq!(totals[""], expected(None));
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 60);
+        assert_eq!(reported.iter().filter(|(tag, _, _)| tag == "seo").map(|(_, credits, _)| credits).sum::<usize>(), totals["seo"]);
+        assert!(reported.iter().all(|(_, credits, q)| *credits == q.len()));
+
+        assert_eq!(client.reset_credits_by_tag(), totals);
+        assert!(client.credits_by_tag().is_empty());
+    }
+
+    #[test]
+    fn search_many_counts_skipped_incidents() {
+        let skipped = || Err(Error::SkippedIncident("incident in progress".to_string()));
+        let outcome = SearchManyOutcome {
+            completed: vec![(3, skipped()), (0, Err(Error::Blocked("frozen".to_string()))), (1, skipped())],
+            over_budget: Vec::new(),
+            not_attempted: vec![2],
+            abandoned: Vec::new(),
+        };
+        assert_eq!(outcome.skipped_incidents(), vec![1, 3]);
+        assert_eq!(outcome.skipped_incident_count(), 2);
+    }
+
+    #[test]
+    fn connection_options() {
+        let overrides = vec![("api.scaleserp.com".to_string(), "203.0.113.7".to_string())];
+        let options = ConnectionOptions::parse(None, true, &overrides).unwrap();
+        assert_eq!(options.local_address, Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+        assert_eq!(options.dns_overrides, vec![("api.scaleserp.com".to_string(), "203.0.113.7:0".parse().unwrap())]);
+        let options = ConnectionOptions::parse(Some("10.0.0.5"), false, &[("a.test".to_string(), "::1".to_string())]).unwrap();
+        assert_eq!(options.local_address, Some("10.0.0.5".parse().unwrap()));
+        assert_eq!(options.dns_overrides[0].1, "[::1]:0".parse().unwrap());
+        assert!(ConnectionOptions::parse(None, false, &[("a.test".to_string(), "127.0.0.1:8443".to_string())]).is_err());
+
+        let error = ScaleSerpClient::builder().api_key("demo").local_address("10.0.0.300").build().err().unwrap();
+        assert_eq!(error.to_string(), "invalid client configuration: local_address \"10.0.0.300\" is not an IP address");
+        let error = ScaleSerpClient::builder().api_key("demo").resolve("api.scaleserp.com", "localhost").build().err().unwrap();
+        assert!(matches!(error, Error::InvalidConfig(_)));
+        assert!(ConnectionOptions::parse(Some("::1"), true, &[]).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore] // listens on 127.0.0.1:443, which needs permission to bind a privileged port
+    async fn resolve_override_reaches_listener() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:443").await.unwrap();
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .resolve("api.scaleserp.com", "127.0.0.1")
+            .build()
+            .unwrap();
+        let accepted = tokio::spawn(async move { listener.accept().await.is_ok() });
+        // TLS fails against the plain listener, but the connection proves the override was used
+        let _ = tokio::time::timeout(Duration::from_secs(2), client.check_connectivity()).await;
+        assert!(tokio::time::timeout(Duration::from_secs(2), accepted).await.unwrap().unwrap());
+    }
+
+    #[test]
+    fn rate_limit_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(RateLimitInfo::from_headers(&headers), RateLimitInfo::default());
+        headers.insert("X-RateLimit-Remaining", "42".parse().unwrap());
+        headers.insert("X-RateLimit-Reset", "1627693237".parse().unwrap());
+        assert_eq!(RateLimitInfo::from_headers(&headers), RateLimitInfo { remaining: Some(42), reset_at: Some(1_627_693_237) });
+        headers.insert("X-RateLimit-Remaining", "lots".parse().unwrap());
+        assert_eq!(RateLimitInfo::from_headers(&headers).remaining, None);
+    }
+
+    #[test]
+    fn connectivity_status_codes() {
+        assert!(connectivity_result(reqwest::StatusCode::OK).is_ok());
+        assert!(matches!(connectivity_result(reqwest::StatusCode::UNAUTHORIZED), Err(Error::InvalidApiKey)));
+        match connectivity_result(reqwest::StatusCode::SERVICE_UNAVAILABLE) {
+            Err(Error::ApiUnavailable(reason)) => assert_eq!(reason, "account endpoint returned 503 Service Unavailable"),
+            _ => panic!("expected ApiUnavailable"),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // calls the live API: needs SCALE_SERP_KEY and network access
+    async fn credits_remaining() {
+        let client = ScaleSerpClient::builder().build().unwrap();
+        assert!(client.get_credits_remaining().await.is_ok());
+    }
+}
@@ -0,0 +1,155 @@
+use serde::de::Error as _;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use crate::schema;
+use crate::search::{Resp, SearchOutcome};
+
+/// How a client parses responses and which optional parameters it sends by default
+/// Pin a dialect so that a change in ScaleSERP's response shape does not silently change your data,
+/// and archive it with the responses (see archive::save_with_dialect) so they can be re-parsed the same way
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseDialect {
+    /// optional blocks that fail to parse are dropped instead of failing the whole response
+    #[default]
+    V1Lenient,
+    /// any block that fails to parse, or that Resp does not model, fails the response
+    V1Strict,
+    /// like V1Lenient, but also asks for the current defaults ScaleSERP recommends, i.e. hide_base64_images
+    Latest,
+}
+
+impl ResponseDialect {
+
+    /// the name of the dialect, as stored in archives
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseDialect::V1Lenient => "v1_lenient",
+            ResponseDialect::V1Strict => "v1_strict",
+            ResponseDialect::Latest => "latest",
+        }
+    }
+
+    /// the dialect named name, see as_str
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "v1_lenient" => Some(ResponseDialect::V1Lenient),
+            "v1_strict" => Some(ResponseDialect::V1Strict),
+            "latest" => Some(ResponseDialect::Latest),
+            _ => None,
+        }
+    }
+
+    /// the parameters added to every search unless they are already set
+    pub fn default_params(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            ResponseDialect::V1Lenient | ResponseDialect::V1Strict => &[],
+            ResponseDialect::Latest => &[("hide_base64_images", "true")],
+        }
+    }
+
+    /// parse the body of a search response
+    pub fn parse(&self, body: &str) -> Result<SearchOutcome, serde_json::Error> {
+        self.parse_value(serde_json::from_str(body)?)
+    }
+
+    /// parse a search response that has already been read as JSON
+    pub fn parse_value(&self, value: Value) -> Result<SearchOutcome, serde_json::Error> {
+        match self {
+            ResponseDialect::V1Strict => {
+                let unmodeled = Resp::unmodeled_blocks(&value);
+                if !unmodeled.is_empty() {
+                    return Err(serde_json::Error::custom(format!("unmodeled blocks: {}", unmodeled.join(", "))));
+                }
+                SearchOutcome::from_value(&value)
+            },
+            ResponseDialect::V1Lenient | ResponseDialect::Latest => parse_lenient(&value),
+        }
+    }
+}
+
+/// parse value, dropping optional blocks that fail to parse
+/// Each optional block is checked on its own next to the required ones, so one bad block never takes a good one with it
+fn parse_lenient(value: &Value) -> Result<SearchOutcome, serde_json::Error> {
+    let error = match SearchOutcome::from_value(value) {
+        Ok(outcome) => return Ok(outcome),
+        Err(e) => e,
+    };
+    let blocks = match value.as_object() {
+        Some(blocks) => blocks,
+        None => return Err(error),
+    };
+    let required: Map<String, Value> = blocks.iter()
+        .filter(|(key, _)| schema::REQUIRED_BLOCKS.contains(&key.as_str()))
+        .map(|(key, block)| (key.clone(), block.clone()))
+        .collect();
+    let mut kept = required.clone();
+    for (key, block) in blocks.iter().filter(|(key, _)| schema::is_supported_block(key) && !required.contains_key(*key)) {
+        let mut alone = required.clone();
+        alone.insert(key.clone(), block.clone());
+        match SearchOutcome::from_value(&Value::Object(alone)) {
+            Ok(_) => { kept.insert(key.clone(), block.clone()); },
+            Err(e) => log::warn!("dropped the {} block, which could not be parsed: {}", key, e),
+        }
+    }
+    SearchOutcome::from_value(&Value::Object(kept)).map_err(|_| error)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_json() -> serde_json::Value {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn strict_and_lenient_differ() {
+        // an ad without a title and a block Resp does not model
+        let mut raw = fixture_json();
+        raw["ads"][0].as_object_mut().unwrap().remove("title");
+        raw["pagination"] = serde_json::json!({"current": 1});
+        let body = raw.to_string();
+
+        let strict = ResponseDialect::V1Strict.parse(&body).unwrap_err();
+        assert!(strict.to_string().starts_with("unmodeled blocks: pagination"));
+
+        let lenient = ResponseDialect::V1Lenient.parse(&body).unwrap().completed().unwrap();
+        assert!(lenient.ads.is_empty() && !lenient.had_ads_block());
+        assert_eq!(lenient.organic_results.len(), 4);
+        assert_eq!(lenient.top_stories.len(), 3);
+
+        // without the unmodeled block, strict parsing still fails on the ad
+        raw.as_object_mut().unwrap().remove("pagination");
+        assert!(ResponseDialect::V1Strict.parse(&raw.to_string()).unwrap_err().to_string().contains("title"));
+    }
+
+    #[test]
+    fn lenient_drops_each_bad_block() {
+        // organic_results is missing from vertical searches, so it is optional and lenient parsing may drop it
+        let mut raw = fixture_json();
+        raw["organic_results"][0].as_object_mut().unwrap().remove("link");
+        let lenient = ResponseDialect::V1Lenient.parse(&raw.to_string()).unwrap().completed().unwrap();
+        assert!(lenient.organic_results.is_empty());
+        assert_eq!(lenient.top_stories.len(), 3);
+
+        // with two bad blocks only those two are dropped
+        raw["ads"][0].as_object_mut().unwrap().remove("title");
+        let lenient = ResponseDialect::V1Lenient.parse_value(raw).unwrap().completed().unwrap();
+        assert!(lenient.organic_results.is_empty() && !lenient.had_ads_block());
+        assert_eq!(lenient.top_stories.len(), 3);
+        assert!(!lenient.related_questions.is_empty() && !lenient.top_products.is_empty());
+    }
+
+    #[test]
+    fn names_and_defaults() {
+        for dialect in [ResponseDialect::V1Lenient, ResponseDialect::V1Strict, ResponseDialect::Latest] {
+            assert_eq!(ResponseDialect::from_name(dialect.as_str()), Some(dialect));
+            assert_eq!(serde_json::to_string(&dialect).unwrap(), format!("\"{}\"", dialect.as_str()));
+        }
+        assert_eq!(ResponseDialect::from_name("v2"), None);
+        assert_eq!(ResponseDialect::default(), ResponseDialect::V1Lenient);
+        assert_eq!(ResponseDialect::Latest.default_params(), &[("hide_base64_images", "true")]);
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use crate::search::{Params, SearchType};
+
+/// The errors that can occur when calling ScaleSERP through a ScaleSerpClient
+#[derive(Debug)]
+pub enum Error {
+    /// the request could not be sent or the response could not be read
+    Http { source: reqwest::Error, context: Option<RequestContext> },
+    /// the response body was not the JSON we expected
+    Json { source: serde_json::Error, context: Option<RequestContext> },
+    /// the API answered with an error status, with the message it sent back
+    Api { status: u16, message: String, context: Option<RequestContext> },
+    /// the API rejected the API key
+    InvalidApiKey,
+    /// the API could not be reached or is not working, with a description of what went wrong
+    ApiUnavailable(String),
+    /// the request URL is longer than the client allows, so it was not sent
+    UrlTooLong { length: usize, limit: usize },
+    /// the request URL could not be parsed
+    InvalidUrl(url::ParseError),
+    /// middleware stopped the request from being sent, with its reason
+    Blocked(String),
+    /// the client was configured with a setting that can not be used, with a description of it
+    InvalidConfig(String),
+    /// required environment variables were not set, with their names
+    MissingEnvVars(Vec<String>),
+    /// an environment variable was set to a value that could not be used
+    InvalidEnvVar { name: String, value: String },
+    /// the query can not be searched for, with the reason. Nothing was sent
+    InvalidQuery(String),
+    /// no location matched the name
+    LocationNotFound(String),
+    /// ScaleSERP skipped the search because of a Google incident, with its explanation
+    /// Nothing was charged, so the search can be retried later. Use ScaleSerpClient::search_outcome to get this as a SearchOutcome
+    SkippedIncident(String),
+    /// several locations matched the name about equally well, with the full names of the best candidates
+    AmbiguousLocation { name: String, candidates: Vec<String> },
+    /// reading or writing a file failed, i.e. an archived response
+    Io(std::io::Error),
+}
+
+impl Error {
+
+    /// attach the request that caused this error
+    pub fn with_context(self, context: RequestContext) -> Self {
+        match self {
+            Error::Http { source, .. } => Error::Http { source, context: Some(context) },
+            Error::Json { source, .. } => Error::Json { source, context: Some(context) },
+            Error::Api { status, message, .. } => Error::Api { status, message, context: Some(context) },
+            other => other,
+        }
+    }
+
+    /// the request that caused this error, if known
+    pub fn context(&self) -> Option<&RequestContext> {
+        match self {
+            Error::Http { context, .. } => context.as_ref(),
+            Error::Json { context, .. } => context.as_ref(),
+            Error::Api { context, .. } => context.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (what, context, source): (&str, &Option<RequestContext>, &dyn fmt::Display) = match self {
+            Error::Http { source, context } => ("request failed", context, source),
+            Error::Json { source, context } => ("deserialization failed", context, source),
+            Error::Api { status, message, context } => {
+                return match context {
+                    Some(context) => write!(f, "the API returned {} for {}: {}", status, context, message),
+                    None => write!(f, "the API returned {}: {}", status, message),
+                };
+            },
+            Error::InvalidApiKey => return write!(f, "the API key was rejected"),
+            Error::ApiUnavailable(reason) => return write!(f, "the API is unavailable: {}", reason),
+            Error::UrlTooLong { length, limit } => return write!(f, "the request URL is {} characters long, over the limit of {}", length, limit),
+            Error::InvalidUrl(e) => return write!(f, "invalid request URL: {}", e),
+            Error::Blocked(reason) => return write!(f, "request blocked: {}", reason),
+            Error::SkippedIncident(message) => return write!(f, "search skipped during a Google incident: {}", message),
+            Error::InvalidConfig(reason) => return write!(f, "invalid client configuration: {}", reason),
+            Error::MissingEnvVars(names) => return write!(f, "missing environment variables: {}", names.join(", ")),
+            Error::InvalidEnvVar { name, value } => return write!(f, "invalid value {:?} for {}", value, name),
+            Error::InvalidQuery(reason) => return write!(f, "invalid query: {}", reason),
+            Error::LocationNotFound(name) => return write!(f, "no location found for {:?}", name),
+            Error::AmbiguousLocation { name, candidates } => return write!(f, "{:?} is ambiguous, candidates: {}", name, candidates.join("; ")),
+            Error::Io(e) => return write!(f, "I/O error: {}", e),
+        };
+        match context {
+            Some(context) => write!(f, "{} for {}: {}", what, context, source),
+            None => write!(f, "{}: {}", what, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::InvalidUrl(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(source: reqwest::Error) -> Self {
+        Error::Http { source, context: None }
+    }
+}
+
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Error::InvalidUrl(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Json { source, context: None }
+    }
+}
+
+
+/// The request an error came from
+/// This never includes the API key, so it is safe to log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    pub q: String,
+    pub location: String, // empty if the search was not geotargeted
+    pub search_type: Option<SearchType>, // None for a regular web search
+}
+
+impl From<&Params> for RequestContext {
+    fn from(params: &Params) -> Self {
+        RequestContext {
+            q: params.q.clone(),
+            location: params.location.clone().unwrap_or_default(),
+            search_type: params.search_type,
+        }
+    }
+}
+
+impl fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "q={:?} location={:?}", self.q, self.location)?;
+        match self.search_type {
+            Some(search_type) => write!(f, " search_type={}", search_type.as_str()),
+            None => Ok(()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Resp;
+
+    #[test]
+    fn display_includes_context() {
+        let json_error = serde_json::from_str::<Resp>("{}").unwrap_err();
+        let error = Error::from(json_error);
+        assert_eq!(error.to_string(), "deserialization failed: missing field `request_info` at line 1 column 2");
+
+        let params = Params::new_env_nyc("external ssd");
+        let error = error.with_context(RequestContext::from(&params));
+        assert_eq!(
+            error.to_string(),
+            "deserialization failed for q=\"external ssd\" location=\"New York,New York,United States\": missing field `request_info` at line 1 column 2"
+        );
+        assert!(!error.to_string().contains("api_key"));
+
+        let news = Params::new_env_nyc("external ssd").with_search_type(SearchType::News);
+        let error = Error::Api { status: 500, message: "internal error".to_string(), context: None }.with_context(RequestContext::from(&news));
+        assert_eq!(
+            error.to_string(),
+            "the API returned 500 for q=\"external ssd\" location=\"New York,New York,United States\" search_type=news: internal error"
+        );
+    }
+}
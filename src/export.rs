@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::report::csv_field;
+use crate::search::{normalize_keyword, Resp};
+
+/// One "People also ask" question, merged across every response it appeared in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FaqEntry {
+    pub question: String, // the first spelling seen
+    pub answer: String, // the answer from the first response the question appeared in
+    pub source_link: String,
+    pub source_title: String,
+    pub seen_in_queries: Vec<String>, // the seed queries the question was asked under, in the order first seen
+}
+
+/// "People also ask" questions and answers from many responses, without duplicates
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FaqExport {
+    pub entries: Vec<FaqEntry>, // in the order questions were first seen
+}
+
+impl FaqExport {
+
+    /// the export as CSV with a header row, one row per question
+    /// The seed queries go in one field separated by " | "
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("question,answer,source_link,source_title,seen_in_queries\n");
+        for entry in &self.entries {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                csv_field(&entry.question),
+                csv_field(&entry.answer),
+                csv_field(&entry.source_link),
+                csv_field(&entry.source_title),
+                csv_field(&entry.seen_in_queries.join(" | ")),
+            ));
+        }
+        csv
+    }
+
+    /// the export as Markdown, one section per question with its answer, source and seed queries
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        for entry in &self.entries {
+            markdown.push_str(&format!("## {}\n\n{}\n\n", entry.question.trim(), entry.answer.trim()));
+            if !entry.source_link.is_empty() {
+                markdown.push_str(&format!("Source: [{}](<{}>)\n\n", entry.source_title.replace(['[', ']'], ""), entry.source_link));
+            }
+            markdown.push_str(&format!("Seen in: {}\n\n", entry.seen_in_queries.join(", ")));
+        }
+        markdown
+    }
+}
+
+/// every related question across responses, merging questions that are the same according to normalize_keyword
+/// so the count agrees with dedup_keywords over the same questions
+pub fn related_questions_to_json(responses: &[Resp]) -> FaqExport {
+    let mut entries: Vec<FaqEntry> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for resp in responses {
+        let query = &resp.search_parameters.q;
+        for question in &resp.related_questions {
+            let i = *index.entry(normalize_keyword(&question.question)).or_insert_with(|| {
+                entries.push(FaqEntry {
+                    question: question.question.clone(),
+                    answer: question.answer.clone(),
+                    source_link: question.source.link.as_str().to_string(),
+                    source_title: question.source.title.clone(),
+                    seen_in_queries: Vec::new(),
+                });
+                entries.len() - 1
+            });
+            let seen = &mut entries[i].seen_in_queries;
+            if !seen.iter().any(|seen| normalize_keyword(seen) == normalize_keyword(query)) {
+                seen.push(query.clone());
+            }
+        }
+    }
+    FaqExport { entries }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::dedup_keywords;
+
+    fn fixture() -> Resp {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn faq_export() {
+        let mut second = fixture();
+        second.search_parameters.q = "portable ssd".to_string();
+        second.related_questions[0].question = "IS AN EXTERNAL  SSD WORTH IT?".to_string();
+        second.related_questions.remove(1);
+        let mut third = fixture();
+        third.search_parameters.q = "External SSD".to_string();
+        let responses = vec![fixture(), second, third];
+
+        let export = related_questions_to_json(&responses);
+        let questions: Vec<&str> = responses.iter()
+            .flat_map(|resp| resp.related_questions.iter().map(|question| question.question.as_str()))
+            .collect();
+        assert_eq!(export.entries.len(), dedup_keywords(&questions).len());
+        assert_eq!(export.entries[0].question, "Is an external SSD worth it?");
+        assert_eq!(export.entries[0].seen_in_queries, vec!["external ssd", "portable ssd"]);
+        assert_eq!(export.entries[1].seen_in_queries, vec!["external ssd"]);
+        assert_eq!(export.entries[1].source_link, "https://www.pcmag.com/how-to/how-long-do-ssds-last");
+
+        let json = serde_json::to_string(&export).unwrap();
+        assert_eq!(serde_json::from_str::<FaqExport>(&json).unwrap(), export);
+
+        let csv = export.to_csv();
+        assert!(csv.starts_with("question,answer,source_link,source_title,seen_in_queries\n"));
+        assert!(csv.contains(",Best External SSDs and Hard Drives 2022 | Tom's Hardware,external ssd | portable ssd\n"));
+        assert_eq!(csv.lines().count(), 3);
+
+        let markdown = export.to_markdown();
+        assert!(markdown.starts_with("## Is an external SSD worth it?\n\nExternal SSDs are much faster"));
+        assert!(markdown.contains("Source: [How Long Do SSDs Last? | PCMag](<https://www.pcmag.com/how-to/how-long-do-ssds-last>)\n\nSeen in: external ssd\n"));
+    }
+}
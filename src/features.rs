@@ -0,0 +1,100 @@
+use serde::{Serialize, Deserialize};
+use crate::search::Resp;
+
+/// Which SERP features a response had, as a bitmap that is cheap to store and compare
+/// A feature counts when its block is in the response and not empty
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(transparent)]
+pub struct SerpFeatures(pub u32);
+
+impl SerpFeatures {
+    pub const ADS: SerpFeatures = SerpFeatures(1);
+    pub const TOP_STORIES: SerpFeatures = SerpFeatures(1 << 1);
+    pub const TOP_PRODUCTS: SerpFeatures = SerpFeatures(1 << 2);
+    pub const RELATED_QUESTIONS: SerpFeatures = SerpFeatures(1 << 3);
+    pub const RELATED_SEARCHES: SerpFeatures = SerpFeatures(1 << 4);
+    pub const APP_RESULTS: SerpFeatures = SerpFeatures(1 << 5);
+    pub const DICTIONARY: SerpFeatures = SerpFeatures(1 << 6);
+    pub const WEATHER: SerpFeatures = SerpFeatures(1 << 7);
+    pub const CONVERSION: SerpFeatures = SerpFeatures(1 << 8);
+    pub const CALCULATOR: SerpFeatures = SerpFeatures(1 << 9);
+    pub const HOTELS: SerpFeatures = SerpFeatures(1 << 10);
+    pub const SPORTS: SerpFeatures = SerpFeatures(1 << 11);
+    pub const FINANCE: SerpFeatures = SerpFeatures(1 << 12);
+
+    /// every feature with its name, in bit order
+    pub const ALL: [(SerpFeatures, &'static str); 13] = [
+        (SerpFeatures::ADS, "ads"),
+        (SerpFeatures::TOP_STORIES, "top_stories"),
+        (SerpFeatures::TOP_PRODUCTS, "top_products"),
+        (SerpFeatures::RELATED_QUESTIONS, "related_questions"),
+        (SerpFeatures::RELATED_SEARCHES, "related_searches"),
+        (SerpFeatures::APP_RESULTS, "app_results"),
+        (SerpFeatures::DICTIONARY, "dictionary"),
+        (SerpFeatures::WEATHER, "weather"),
+        (SerpFeatures::CONVERSION, "conversion"),
+        (SerpFeatures::CALCULATOR, "calculator"),
+        (SerpFeatures::HOTELS, "hotels"),
+        (SerpFeatures::SPORTS, "sports"),
+        (SerpFeatures::FINANCE, "finance"),
+    ];
+
+    /// the features of resp
+    pub fn of(resp: &Resp) -> Self {
+        let present = [
+            !resp.ads.is_empty(),
+            !resp.top_stories.is_empty(),
+            !resp.top_products.is_empty(),
+            !resp.related_questions.is_empty(),
+            !resp.related_searches.is_empty(),
+            resp.app_results.as_ref().is_some_and(|results| !results.is_empty()),
+            resp.dictionary_result.is_some(),
+            resp.weather_result.is_some(),
+            resp.conversion_result.is_some(),
+            resp.calculator_result.is_some(),
+            resp.hotel_results.as_ref().is_some_and(|results| !results.is_empty()),
+            resp.sports_results.is_some(),
+            resp.finance_result.is_some(),
+        ];
+        let bits = present.iter().enumerate()
+            .filter(|(_, present)| **present)
+            .fold(0, |bits, (i, _)| bits | (1 << i));
+        SerpFeatures(bits)
+    }
+
+    /// true if every feature in other is also in self
+    pub fn contains(&self, other: SerpFeatures) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// the names of the features, in bit order
+    pub fn names(&self) -> Vec<&'static str> {
+        SerpFeatures::ALL.iter()
+            .filter(|(feature, _)| self.contains(*feature))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl std::ops::BitOr for SerpFeatures {
+    type Output = SerpFeatures;
+    fn bitor(self, other: SerpFeatures) -> SerpFeatures {
+        SerpFeatures(self.0 | other.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_features() {
+        let resp: Resp = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+        let features = SerpFeatures::of(&resp);
+        assert_eq!(features.names(), vec!["ads", "top_stories", "top_products", "related_questions", "related_searches"]);
+        assert!(features.contains(SerpFeatures::ADS | SerpFeatures::TOP_STORIES));
+        assert!(!features.contains(SerpFeatures::WEATHER));
+        assert_eq!(serde_json::to_string(&features).unwrap(), "31");
+    }
+}
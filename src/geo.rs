@@ -0,0 +1,106 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+
+/// the mean radius of the Earth in meters
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// A point on the Earth, in degrees
+/// Deserializing fails if latitude is outside -90..=90 or longitude outside -180..=180
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "UncheckedCoordinates")]
+pub struct GpsCoordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// GpsCoordinates as they appear in a payload, before their range is checked
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct UncheckedCoordinates {
+    latitude: f64,
+    longitude: f64,
+}
+
+impl TryFrom<UncheckedCoordinates> for GpsCoordinates {
+    type Error = String;
+    fn try_from(unchecked: UncheckedCoordinates) -> Result<Self, String> {
+        GpsCoordinates::new(unchecked.latitude, unchecked.longitude)
+    }
+}
+
+impl GpsCoordinates {
+
+    /// coordinates, checking latitude and longitude are in range
+    pub fn new(latitude: f64, longitude: f64) -> Result<Self, String> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(format!("latitude {} is outside -90 to 90", latitude));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(format!("longitude {} is outside -180 to 180", longitude));
+        }
+        Ok(GpsCoordinates { latitude, longitude })
+    }
+
+    /// the great-circle distance to other in meters (haversine formula)
+    pub fn distance_to(&self, other: &GpsCoordinates) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let half_dlat = (lat2 - lat1) / 2.0;
+        let half_dlon = (other.longitude - self.longitude).to_radians() / 2.0;
+        let a = half_dlat.sin().powi(2) + lat1.cos() * lat2.cos() * half_dlon.sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+    }
+}
+
+impl fmt::Display for GpsCoordinates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.latitude, self.longitude)
+    }
+}
+
+impl FromStr for GpsCoordinates {
+    type Err = String;
+
+    /// parse "lat,lon", i.e. "40.7128,-74.006"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (latitude, longitude) = s.split_once(',').ok_or_else(|| format!("{:?} is not \"lat,lon\"", s))?;
+        let parse = |value: &str| value.trim().parse::<f64>().map_err(|_| format!("{:?} is not a number", value.trim()));
+        GpsCoordinates::new(parse(latitude)?, parse(longitude)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> GpsCoordinates {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn city_distances() {
+        let km = |a: &str, b: &str| at(a).distance_to(&at(b)) / 1000.0;
+        let new_york_london = km("40.7128,-74.0060", "51.5074,-0.1278");
+        assert!((new_york_london - 5570.0).abs() < 5.0, "{}", new_york_london);
+        let paris_berlin = km("48.8566,2.3522", "52.5200,13.4050");
+        assert!((paris_berlin - 878.0).abs() < 5.0, "{}", paris_berlin);
+        let sydney_melbourne = km("-33.8688,151.2093", "-37.8136,144.9631");
+        assert!((sydney_melbourne - 714.0).abs() < 5.0, "{}", sydney_melbourne);
+        assert_eq!(at("10,20").distance_to(&at("10,20")), 0.0);
+    }
+
+    #[test]
+    fn parse_display_and_validate() {
+        let coordinates = at(" 40.7128 , -74.006 ");
+        assert_eq!(coordinates.to_string(), "40.7128,-74.006");
+        assert_eq!(coordinates.to_string().parse::<GpsCoordinates>(), Ok(coordinates));
+        assert!("40.7128".parse::<GpsCoordinates>().is_err());
+        assert_eq!("91,0".parse::<GpsCoordinates>(), Err("latitude 91 is outside -90 to 90".to_string()));
+
+        let valid: GpsCoordinates = serde_json::from_str(r#"{"latitude": 51.5, "longitude": -0.12}"#).unwrap();
+        assert_eq!(valid, GpsCoordinates { latitude: 51.5, longitude: -0.12 });
+        let invalid = serde_json::from_str::<GpsCoordinates>(r#"{"latitude": 51.5, "longitude": 190}"#).unwrap_err();
+        assert!(invalid.to_string().contains("longitude 190 is outside -180 to 180"));
+    }
+}
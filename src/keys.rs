@@ -0,0 +1,247 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::error::Error;
+use crate::search::RequestInfo;
+
+/// how long a key that was rejected or ran out of credits is left out, unless set with KeyPool::with_cooldown
+pub const DEFAULT_KEY_COOLDOWN: Duration = Duration::from_secs(15 * 60);
+
+/// How a KeyPool picks the key for the next request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeySelection {
+    #[default]
+    RoundRobin, // each key in turn
+    LeastRecentlyUsed, // the key that has waited longest since its last request
+}
+
+/// Several API keys that a ScaleSerpClient spreads its searches across, see ScaleSerpClientBuilder::key_pool
+/// Keys that are rejected or run out of credits are left out for a cooldown, after which the next request through them
+/// checks whether they work again. Keys are only ever reported by their label, never by the key itself
+pub struct KeyPool {
+    keys: Vec<PooledKey>,
+    selection: KeySelection,
+    cooldown: Duration,
+    min_interval: Option<Duration>, // per key
+    state: Mutex<PoolState>,
+}
+
+struct PooledKey {
+    label: String,
+    key: String,
+}
+
+#[derive(Default)]
+struct PoolState {
+    next: usize, // where round robin selection continues from
+    keys: Vec<KeyState>,
+}
+
+#[derive(Default, Clone)]
+struct KeyState {
+    requests: usize,
+    last_start: Option<Instant>,
+    credits_remaining: Option<usize>,
+    disabled_until: Option<Instant>,
+}
+
+/// The key picked for one request
+pub struct KeyLease {
+    pub index: usize,
+    pub label: String,
+    pub wait: Duration, // how long to wait before sending, so the key keeps to its min_interval
+    key: String,
+}
+
+impl KeyLease {
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// How one key in a pool has been used, safe to log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyStats {
+    pub label: String,
+    pub requests: usize,
+    pub credits_remaining: Option<usize>, // from the last response served with the key
+    pub cooling_down: bool,
+}
+
+impl KeyPool {
+
+    pub fn new() -> Self {
+        KeyPool {
+            keys: Vec::new(),
+            selection: KeySelection::default(),
+            cooldown: DEFAULT_KEY_COOLDOWN,
+            min_interval: None,
+            state: Mutex::new(PoolState::default()),
+        }
+    }
+
+    /// add a key, with a label to report it by, i.e. "marketing account"
+    pub fn with_key(mut self, label: &str, key: &str) -> Self {
+        self.keys.push(PooledKey { label: label.to_string(), key: key.to_string() });
+        self.state.get_mut().unwrap().keys.push(KeyState::default());
+        self
+    }
+
+    pub fn with_selection(mut self, selection: KeySelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// leave rejected or exhausted keys out for cooldown (DEFAULT_KEY_COOLDOWN by default)
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// leave at least min_interval between the starts of consecutive requests with the same key
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = Some(min_interval);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// pick the key for the next request, or Error::Blocked if every key is cooling down
+    pub fn acquire(&self) -> Result<KeyLease, Error> {
+        if self.keys.is_empty() {
+            return Err(Error::InvalidConfig("the key pool has no keys".to_string()));
+        }
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        for key in state.keys.iter_mut() {
+            if key.disabled_until.is_some_and(|until| until <= now) {
+                key.disabled_until = None; // the next request through it checks whether it works again
+            }
+        }
+        let available = |i: &usize| state.keys[*i].disabled_until.is_none();
+        let count = state.keys.len();
+        let index = match self.selection {
+            KeySelection::RoundRobin => (0..count).map(|offset| (state.next + offset) % count).find(available),
+            KeySelection::LeastRecentlyUsed => (0..count).filter(available).min_by_key(|i| state.keys[*i].last_start),
+        };
+        let index = index.ok_or_else(|| Error::Blocked("every key in the pool is cooling down".to_string()))?;
+        state.next = (index + 1) % count;
+        let key = &mut state.keys[index];
+        let start = match (key.last_start, self.min_interval) {
+            (Some(last), Some(interval)) => (last + interval).max(now),
+            _ => now,
+        };
+        key.last_start = Some(start);
+        key.requests += 1;
+        Ok(KeyLease {
+            index,
+            label: self.keys[index].label.clone(),
+            wait: start - now,
+            key: self.keys[index].key.clone(),
+        })
+    }
+
+    /// record the credits left on a key after a successful request, cooling it down if there are none
+    pub fn report_success(&self, index: usize, request_info: &RequestInfo) {
+        let mut state = self.state.lock().unwrap();
+        let key = &mut state.keys[index];
+        key.credits_remaining = Some(request_info.credits_remaining);
+        if request_info.credits_remaining == 0 {
+            key.disabled_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// cool a key down if error says it was rejected or is out of credits
+    pub fn report_error(&self, index: usize, error: &Error) {
+        if is_key_error(error) {
+            log::warn!("leaving key {:?} out for {:?}: {}", self.keys[index].label, self.cooldown, error);
+            self.state.lock().unwrap().keys[index].disabled_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// how each key has been used, in the order they were added
+    pub fn stats(&self) -> Vec<KeyStats> {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        self.keys.iter().zip(state.keys.iter()).map(|(pooled, key)| KeyStats {
+            label: pooled.label.clone(),
+            requests: key.requests,
+            credits_remaining: key.credits_remaining,
+            cooling_down: key.disabled_until.is_some_and(|until| until > now),
+        }).collect()
+    }
+}
+
+impl Default for KeyPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// true if error means the key itself can not be used right now
+fn is_key_error(error: &Error) -> bool {
+    match error {
+        Error::InvalidApiKey => true,
+        Error::Api { status, message, .. } => (401..=403).contains(status) || message.to_lowercase().contains("credits"),
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(selection: KeySelection) -> KeyPool {
+        KeyPool::new().with_key("a", "key-a").with_key("b", "key-b").with_key("c", "key-c").with_selection(selection)
+    }
+
+    fn labels(pool: &KeyPool, n: usize) -> Vec<String> {
+        (0..n).map(|_| pool.acquire().unwrap().label).collect()
+    }
+
+    fn request_info(credits_remaining: usize) -> RequestInfo {
+        RequestInfo { success: true, credits_used: 1, credits_used_this_request: 1, credits_remaining, credits_reset_at: String::new() }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn round_robin_and_cooldown() {
+        let pool = pool(KeySelection::RoundRobin).with_cooldown(Duration::from_millis(50));
+        assert_eq!(labels(&pool, 4), vec!["a", "b", "c", "a"]);
+        assert_eq!(pool.acquire().unwrap().key(), "key-b");
+
+        pool.report_error(1, &Error::Api { status: 401, message: "invalid api_key".to_string(), context: None });
+        pool.report_success(2, &request_info(0));
+        pool.report_error(0, &Error::Blocked("not the key's fault".to_string()));
+        assert_eq!(labels(&pool, 2), vec!["a", "a"]);
+        let stats = pool.stats();
+        assert!(!stats[0].cooling_down && stats[1].cooling_down && stats[2].cooling_down);
+        assert_eq!(stats[2].credits_remaining, Some(0));
+
+        pool.report_error(0, &Error::InvalidApiKey);
+        assert!(matches!(pool.acquire(), Err(Error::Blocked(_))));
+
+        tokio::time::advance(Duration::from_millis(49)).await;
+        assert!(pool.acquire().is_err());
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(labels(&pool, 3).len(), 3); // every key is probed again after the cooldown
+    }
+
+    #[test]
+    fn least_recently_used_and_min_interval() {
+        let pool = pool(KeySelection::LeastRecentlyUsed).with_min_interval(Duration::from_secs(1));
+        let leases: Vec<KeyLease> = (0..4).map(|_| pool.acquire().unwrap()).collect();
+        let labels: Vec<&str> = leases.iter().map(|lease| lease.label.as_str()).collect();
+        assert_eq!(labels, vec!["a", "b", "c", "a"]);
+        assert!(leases[..3].iter().all(|lease| lease.wait.is_zero()));
+        assert!(leases[3].wait > Duration::from_millis(900)); // "a" was used less than a second ago
+        assert_eq!(pool.stats()[0].requests, 2);
+    }
+}
@@ -0,0 +1,8 @@
+/// Detects which language a snippet is written in, for checking results against the `hl` a search was sent with
+/// Google returns pages in other languages when it has too few in the requested one, i.e. "de" results for a
+/// rare query sent with hl=en. language_mismatches and OrganicResult::detected_language use this to find them
+pub trait LanguageDetector {
+    /// the ISO 639-1 code of the language text is written in, lowercase like `hl` (i.e. "en", "de", "ja")
+    /// None when the text is too short or mixed to tell: those results are never reported as mismatches
+    fn detect(&self, text: &str) -> Option<String>;
+}
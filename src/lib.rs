@@ -1,5 +1,37 @@
 pub mod locations; // for finding locations
 pub mod search; // for performing searches 
+pub mod archive; // for saving responses to disk
+pub mod language; // for detecting the language of results
+pub mod authority; // for scoring results with page authority providers
+pub mod link; // for comparing and normalizing result links
+pub mod client; // a reusable client for calling the API
+pub mod error; // errors returned by the client
+pub mod report; // for reports across many responses
+pub mod middleware; // hooks that run around every client call
+pub mod budget; // for planning credit usage
+pub mod schema; // which response blocks are modeled
+pub mod account; // for checking credits without searching
+pub mod stats; // aggregates across a corpus of responses
+pub mod dialect; // pinning how responses are parsed
+pub mod suggestions; // tracking how suggestions for a seed change
+pub mod vertical; // the kinds of search and which results they return
+pub mod geo; // coordinates and distances
+pub mod features; // a bitmap of the SERP features in a response
+pub mod rank; // rank history rows for tracked domains
+pub mod summary; // small summaries of responses for bulk storage
+pub mod market; // grouping searches and responses by market
+pub mod operators; // composing queries with search operators
+pub mod template; // queries with placeholders filled in per search
+pub mod prune; // shrinking responses before storing them
+pub mod parse; // parsing responses with caps on long blocks
+pub mod export; // "People also ask" questions as FAQ data
+pub mod keys; // spreading searches across several API keys
+#[cfg(feature = "worker")]
+pub mod worker; // a scheduler for running searches periodically
+mod query; // URL encoding and redaction shared by Params and LocReqConfig
+
+/// A boxed error for async calls that can fail in several ways (network, deserialization, etc.)
+pub type GenericError = Box<dyn std::error::Error + Send + Sync>;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use serde::{Serialize, Deserialize};
+use url::Url;
+
+/// query parameters that only track where a click came from and never change the page
+const TRACKING_PARAMS: [&str; 5] = ["gclid", "fbclid", "msclkid", "dclid", "yclid"];
+
+/// public suffixes ResultLink::root_domain knows, a short list of the common ones rather than the whole public suffix list
+const KNOWN_SUFFIXES: [&str; 78] = [
+    "com", "org", "net", "edu", "gov", "mil", "int", "info", "biz", "io", "co", "ai", "app", "dev", "me", "tv",
+    "us", "ca", "mx", "br", "ar", "uk", "ie", "de", "fr", "es", "it", "nl", "be", "ch", "at", "se", "no", "dk",
+    "fi", "pl", "cz", "pt", "gr", "ru", "ua", "tr", "il", "in", "cn", "jp", "kr", "tw", "hk", "sg", "au", "nz", "za",
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "me.uk", "ltd.uk", "plc.uk",
+    "com.au", "net.au", "org.au", "edu.au", "gov.au",
+    "co.nz", "org.nz", "co.jp", "ne.jp", "or.jp", "ac.jp", "co.kr", "co.in", "co.za",
+    "com.br", "com.mx", "com.ar", "com.cn",
+];
+
+/// a domain without surrounding whitespace, "www." or a trailing dot, in lowercase
+/// i.e. "example.com" for "WWW.Example.com"
+pub fn normalize_domain(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    match domain.strip_prefix("www.") {
+        Some(stripped) => stripped.to_string(),
+        None => domain,
+    }
+}
+
+/// true if host is domain or one of its subdomains, ignoring case and "www."
+/// i.e. "shop.example.com" and "www.example.com" both match "example.com", but "myexample.com" does not
+pub fn domain_matches(host: &str, domain: &str) -> bool {
+    let host = normalize_domain(host);
+    let domain = normalize_domain(domain);
+    !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+/// The link of a search result
+/// This wraps the raw string returned by ScaleSERP, but two links compare equal when their normalized forms match,
+/// so "https://www.example.com/a/?utm_source=x" == "http://example.com/a"
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ResultLink(String);
+
+impl ResultLink {
+
+    /// the link exactly as returned by the API
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// the link parsed as a URL, for its path segments, query parameters or fragment
+    pub fn parsed(&self) -> Result<Url, url::ParseError> {
+        Url::parse(&self.0)
+    }
+
+    /// the lowercase host of the link, i.e. "www.example.com"
+    pub fn host(&self) -> Option<String> {
+        let url = Url::parse(&self.0).ok()?;
+        url.host_str().map(|host| host.to_lowercase())
+    }
+
+    /// true if the link is on domain or one of its subdomains, see domain_matches
+    pub fn is_on_domain(&self, domain: &str) -> bool {
+        match self.host() {
+            Some(host) => domain_matches(&host, domain),
+            None => false,
+        }
+    }
+
+    /// the registrable domain of the link, i.e. "example.com" for "https://shop.example.com/a" and "bbc.co.uk" for "https://www.bbc.co.uk/news"
+    /// None if the host is an IP address or ends in a suffix the crate does not know (see KNOWN_SUFFIXES), rather than a guess
+    pub fn root_domain(&self) -> Option<String> {
+        let host = self.host()?;
+        let host = host.trim_end_matches('.');
+        let labels: Vec<&str> = host.split('.').collect();
+        // the longest known suffix, which needs a label in front of it
+        let start = (0..labels.len()).find(|start| KNOWN_SUFFIXES.contains(&labels[*start..].join(".").as_str()))?;
+        (start > 0).then(|| labels[start - 1..].join("."))
+    }
+
+    /// the link without scheme, "www.", fragment, trailing slash or tracking parameters (utm_*, gclid etc.)
+    /// i.e. "example.com/a?id=1" for "https://www.example.com/a/?id=1&utm_source=x#top"
+    /// Links that cannot be parsed are returned trimmed and lowercased
+    pub fn normalized(&self) -> String {
+        let url = match Url::parse(self.0.trim()) {
+            Ok(url) => url,
+            Err(_) => return self.0.trim().to_lowercase(),
+        };
+        let host = url.host_str().unwrap_or("").to_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+        let mut normalized = host.to_string();
+        if let Some(port) = url.port() {
+            normalized.push_str(&format!(":{}", port));
+        }
+        normalized.push_str(url.path().trim_end_matches('/'));
+        let query: Vec<String> = url.query_pairs()
+            .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        if !query.is_empty() {
+            normalized.push('?');
+            normalized.push_str(&query.join("&"));
+        }
+        normalized
+    }
+}
+
+impl PartialEq for ResultLink {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for ResultLink {}
+
+impl Hash for ResultLink {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+impl Deref for ResultLink {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ResultLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for ResultLink {
+    fn from(link: String) -> Self {
+        ResultLink(link)
+    }
+}
+
+impl From<&str> for ResultLink {
+    fn from(link: &str) -> Self {
+        ResultLink(link.to_string())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization() {
+        let link = ResultLink::from("https://WWW.Example.com/a/b/?id=1&utm_source=news&gclid=xyz#reviews");
+        assert_eq!(link.host(), Some("www.example.com".to_string()));
+        assert_eq!(link.root_domain(), Some("example.com".to_string()));
+        assert_eq!(link.normalized(), "example.com/a/b?id=1");
+        assert_eq!(link, ResultLink::from("http://example.com/a/b?id=1"));
+        assert_ne!(link, ResultLink::from("https://example.com/a/b?id=2"));
+        assert_eq!(ResultLink::from("https://shop.example.com/").normalized(), "shop.example.com");
+        assert_eq!(ResultLink::from("not a url").host(), None);
+        assert_eq!(link.as_str(), "https://WWW.Example.com/a/b/?id=1&utm_source=news&gclid=xyz#reviews");
+    }
+
+    #[test]
+    fn domains() {
+        assert_eq!(normalize_domain(" WWW.Example.com. "), "example.com");
+        assert!(domain_matches("www.example.com", "example.com"));
+        assert!(domain_matches("shop.example.com", "www.example.com"));
+        assert!(!domain_matches("myexample.com", "example.com"));
+        assert!(!domain_matches("example.com", ""));
+        assert!(ResultLink::from("https://shop.example.com/a").is_on_domain("example.com"));
+
+        let root = |link: &str| ResultLink::from(link).root_domain();
+        assert_eq!(root("https://www.bbc.co.uk/news"), Some("bbc.co.uk".to_string()));
+        assert_eq!(root("https://news.bbc.co.uk/"), Some("bbc.co.uk".to_string()));
+        assert_eq!(root("https://example.com/"), Some("example.com".to_string()));
+        assert_eq!(root("https://shop.example.com.au/"), Some("example.com.au".to_string()));
+        assert_eq!(root("https://example.uk/"), Some("example.uk".to_string()));
+        // an unknown suffix, a bare suffix and an IP address have no root domain the crate can vouch for
+        assert_eq!(root("https://shop.example.zz/"), None);
+        assert_eq!(root("https://co.uk/"), None);
+        assert_eq!(root("http://192.168.0.1/"), None);
+    }
+
+    #[test]
+    fn serde_is_transparent() {
+        let link: ResultLink = serde_json::from_str("\"https://www.example.com/\"").unwrap();
+        assert_eq!(&*link, "https://www.example.com/");
+        assert_eq!(serde_json::to_string(&link).unwrap(), "\"https://www.example.com/\"");
+    }
+}
@@ -1,13 +1,26 @@
 use std::{vec::Vec, env};
+use std::collections::HashSet;
+use futures_util::stream::{self, StreamExt};
 use serde::Deserialize;
+use crate::GenericError;
+use crate::client::ScaleSerpClient;
+use crate::error::Error;
+pub use crate::geo::GpsCoordinates;
+use crate::query;
+use crate::search::{normalize_keyword, Params};
+
+/// how many location lookups resolve_many and fetch_types run at once
+const RESOLVE_CONCURRENCY: usize = 8;
 
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct RequestInfo {
     pub success: bool
 }
 
 /// This is the response that gets sent back when you call the location API
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct LocationResp {
     pub request_info: RequestInfo,
     pub locations_total: i32,
@@ -17,11 +30,23 @@ pub struct LocationResp {
     pub locations: Vec<Location>,
 }
 
-#[derive(Deserialize)]
+impl LocationResp {
+
+    /// a search for q from each location, using the environment variable SCALE_SERP_KEY
+    /// i.e. `resp.into_params_iter("coffee shops")` to compare results across cities
+    pub fn into_params_iter(self, q: &str) -> impl Iterator<Item = Params> {
+        let q = q.to_string();
+        self.locations.into_iter().map(move |location| Params::new_env(&q, &location.full_name))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
 pub struct Location {
     pub id: i32,
     pub name: String,
-    pub r#type: String,
+    #[serde(rename = "type")]
+    pub r#type: String, // i.e. "City" or "Country"
     pub full_name: String,
     pub parent_id: i32,
     pub country_code: String,
@@ -29,23 +54,58 @@ pub struct Location {
     pub gps_coordinates: GpsCoordinates,
 }
 
-#[derive(Deserialize)]
-pub struct GpsCoordinates {
-    pub latitude: f64,
-    pub longitude: f64,
-}
-
 /// This struct is the configuration for a request to the location API
+/// Build one with LocReqConfig::builder, i.e. `LocReqConfig::builder("boston").location_type(LocationType::City).build()`
 pub struct LocReqConfig {
+    #[deprecated(note = "set with LocReqConfigBuilder::api_key instead")]
     pub api_key: String, // your scale SERP access key
+    #[deprecated(note = "set with LocReqConfig::builder instead")]
     pub q: String,   // the query string to send
+    #[deprecated(note = "set with LocReqConfigBuilder::location_type instead")]
     pub r#type: Option<String>, // i.e. 'city' for a city 
+    #[deprecated(note = "set with LocReqConfigBuilder::country_code instead")]
     pub country_code: Option<String>,
+    limit: Option<usize>,
+    page: Option<usize>,
 }
 
+/// The kind of place to look up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LocationType {
+    Country,
+    State,
+    Region,
+    County,
+    City,
+    Neighborhood,
+    PostalCode,
+}
+
+impl LocationType {
 
+    /// the value of the type parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocationType::Country => "country",
+            LocationType::State => "state",
+            LocationType::Region => "region",
+            LocationType::County => "county",
+            LocationType::City => "city",
+            LocationType::Neighborhood => "neighborhood",
+            LocationType::PostalCode => "postal_code",
+        }
+    }
+}
+
+
+#[allow(deprecated)] // the fields stay public until the next breaking release
 impl LocReqConfig {
 
+    /// start building a config to look up q
+    pub fn builder(q: &str) -> LocReqConfigBuilder {
+        LocReqConfigBuilder::new(q)
+    }
+
     /// create a new config using the environment variable SCALE_SERP_KEY
     pub fn new_from_env(q: &str) -> Self {
         let api_key: String = env::var("SCALE_SERP_KEY").unwrap();
@@ -58,31 +118,330 @@ impl LocReqConfig {
             api_key: api_key.to_string(),
             q: q.to_string(),
             r#type: None,
-            country_code: None
+            country_code: None,
+            limit: None,
+            page: None,
         }
     }
     
     /// generate the url you want to call
     pub fn to_url(&self) -> String {
-        let mut url = format!("https://api.scaleserp.com/locations?api_key={}&q={}", self.api_key, self.q);
-        match &self.r#type {
-            None => {},
-            Some(tpe) => {
-                url.push('&');
-                url.push_str(tpe);
-            }
+        let mut url = format!("https://api.scaleserp.com/locations?api_key={}", query::encode(&self.api_key));
+        query::push_param(&mut url, "q", &self.q);
+        if let Some(tpe) = &self.r#type {
+            query::push_param(&mut url, "type", tpe);
         }
-        match &self.country_code {
-            None => {},
-            Some(cc) => {
-                url.push('&');
-                url.push_str(cc);
-            }
+        if let Some(cc) = &self.country_code {
+            query::push_param(&mut url, "country_code", cc);
+        }
+        if let Some(limit) = self.limit {
+            query::push_param(&mut url, "limit", &limit.to_string());
+        }
+        if let Some(page) = self.page {
+            query::push_param(&mut url, "page", &page.to_string());
         }
         url 
     }
 
+    /// the URL with the API key replaced by "***", safe to log
+    pub fn redacted_url(&self) -> String {
+        query::redact_api_key(&self.to_url())
+    }
+
+    /// send the request to the location API
     pub async fn send(&self) -> Result<LocationResp, GenericError> {
-        CONTINUE HERE 
+        let resp: LocationResp = reqwest::get(&self.to_url())
+            .await?
+            .json()
+            .await?;
+        Ok(resp)
+    }
+}
+
+
+/// Builds a LocReqConfig
+pub struct LocReqConfigBuilder {
+    config: LocReqConfig,
+}
+
+#[allow(deprecated)] // the builder is how the deprecated fields should be set
+impl LocReqConfigBuilder {
+
+    /// a builder for looking up q, using the environment variable SCALE_SERP_KEY unless api_key is called
+    pub fn new(q: &str) -> Self {
+        let api_key = env::var("SCALE_SERP_KEY").unwrap_or_default();
+        LocReqConfigBuilder { config: LocReqConfig::new(&api_key, q) }
+    }
+
+    pub fn api_key(mut self, api_key: &str) -> Self {
+        self.config.api_key = api_key.to_string();
+        self
+    }
+
+    /// only return locations of this type
+    pub fn location_type(mut self, location_type: LocationType) -> Self {
+        self.config.r#type = Some(location_type.as_str().to_string());
+        self
     }
-}
\ No newline at end of file
+
+    /// only return locations in this country, i.e. "us"
+    pub fn country_code(mut self, country_code: &str) -> Self {
+        self.config.country_code = Some(country_code.to_string());
+        self
+    }
+
+    /// return at most limit locations per page
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.config.limit = Some(limit);
+        self
+    }
+
+    /// the page of results to return, starting at 1
+    pub fn page(mut self, page: usize) -> Self {
+        self.config.page = Some(page);
+        self
+    }
+
+    pub fn build(self) -> LocReqConfig {
+        self.config
+    }
+}
+
+
+/// Resolve many place names to locations at once, i.e. a list of configured cities at startup
+/// Lookups run concurrently and the results are in the same order as names
+/// A name that matches several places about equally well gives Error::AmbiguousLocation rather than a guess
+pub async fn resolve_many(client: &ScaleSerpClient, names: &[&str], country_code: Option<&str>) -> Vec<Result<Location, Error>> {
+    stream::iter(names.iter())
+        .map(|name| async move {
+            let mut builder = LocReqConfig::builder(name).api_key(client.api_key());
+            if let Some(country_code) = country_code {
+                builder = builder.country_code(country_code);
+            }
+            let config = builder.build();
+            let resp = client.locations(&config).await?;
+            best_match(name, resp.locations)
+        })
+        .buffered(RESOLVE_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Look up q restricted to any of several location types, i.e. cities or postal codes, in one call
+/// The API takes a single type per request, so this looks each type up concurrently and merges the results,
+/// see merge_locations. The first failed lookup is returned as the error
+pub async fn fetch_types(client: &ScaleSerpClient, q: &str, types: &[LocationType], country_code: Option<&str>) -> Result<Vec<Location>, Error> {
+    let responses: Vec<Result<LocationResp, Error>> = stream::iter(types.iter())
+        .map(|location_type| async move {
+            let mut builder = LocReqConfig::builder(q).api_key(client.api_key()).location_type(*location_type);
+            if let Some(country_code) = country_code {
+                builder = builder.country_code(country_code);
+            }
+            client.locations(&builder.build()).await
+        })
+        .buffered(RESOLVE_CONCURRENCY)
+        .collect()
+        .await;
+    let mut sets = Vec::with_capacity(responses.len());
+    for response in responses {
+        sets.push(response?.locations);
+    }
+    Ok(merge_locations(sets))
+}
+
+/// merge several lists of locations into one without duplicate ids, by reach descending and then id
+pub fn merge_locations(sets: Vec<Vec<Location>>) -> Vec<Location> {
+    let mut seen = HashSet::new();
+    let mut merged: Vec<Location> = sets.into_iter()
+        .flatten()
+        .filter(|location| seen.insert(location.id))
+        .collect();
+    merged.sort_by_key(|location| (std::cmp::Reverse(location.reach), location.id));
+    merged
+}
+
+/// the location closest to point, None if locations is empty
+pub fn nearest_location<'a>(locations: &'a [Location], point: &GpsCoordinates) -> Option<&'a Location> {
+    locations.iter().min_by(|a, b| a.gps_coordinates.distance_to(point).total_cmp(&b.gps_coordinates.distance_to(point)))
+}
+
+/// pick the location that best matches name
+/// Locations whose name matches exactly (ignoring case in any script and extra whitespace) are preferred, then the one with the largest reach,
+/// as long as it reaches at least twice as many people as the runner up
+fn best_match(name: &str, locations: Vec<Location>) -> Result<Location, Error> {
+    let wanted = normalize_keyword(name);
+    let exact: Vec<Location> = locations.iter()
+        .filter(|location| normalize_keyword(&location.name) == wanted)
+        .cloned()
+        .collect();
+    let mut candidates = if exact.is_empty() { locations } else { exact };
+    candidates.sort_by_key(|location| std::cmp::Reverse(location.reach));
+    match candidates.len() {
+        0 => Err(Error::LocationNotFound(name.to_string())),
+        1 => Ok(candidates.remove(0)),
+        _ if candidates[0].reach >= candidates[1].reach.saturating_mul(2) => Ok(candidates.remove(0)),
+        _ => Err(Error::AmbiguousLocation {
+            name: name.to_string(),
+            candidates: candidates.iter().take(5).map(|location| location.full_name.clone()).collect(),
+        }),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(id: i32, name: &str, full_name: &str, reach: u32) -> Location {
+        Location {
+            id,
+            name: name.to_string(),
+            r#type: "City".to_string(),
+            full_name: full_name.to_string(),
+            parent_id: 0,
+            country_code: "US".to_string(),
+            reach,
+            gps_coordinates: GpsCoordinates { latitude: 0.0, longitude: 0.0 },
+        }
+    }
+
+    #[test]
+    fn best_match_ranking() {
+        let springfields = vec![
+            location(1, "Springfield", "Springfield,Illinois,United States", 300_000),
+            location(2, "Springfield", "Springfield,Missouri,United States", 280_000),
+            location(3, "Springfield Gardens", "Springfield Gardens,New York,United States", 900_000),
+        ];
+        match best_match("springfield", springfields) {
+            Err(Error::AmbiguousLocation { candidates, .. }) => assert_eq!(candidates, vec![
+                "Springfield,Illinois,United States",
+                "Springfield,Missouri,United States",
+            ]),
+            other => panic!("expected AmbiguousLocation, got {:?}", other.map(|l| l.id)),
+        }
+
+        let bostons = vec![
+            location(4, "Boston", "Boston,Lincolnshire,United Kingdom", 60_000),
+            location(5, "Boston", "Boston,Massachusetts,United States", 4_000_000),
+        ];
+        assert_eq!(best_match("Boston", bostons).unwrap().id, 5);
+
+        let partial = vec![location(6, "Boston Heights", "Boston Heights,Ohio,United States", 1_000)];
+        assert_eq!(best_match("Boston", partial).unwrap().id, 6);
+        assert!(matches!(best_match("Atlantis", Vec::new()), Err(Error::LocationNotFound(_))));
+
+        // without the exact match the larger Munich Airport area would be picked
+        let munichs = vec![
+            location(7, "Munich Airport", "Munich Airport,Bavaria,Germany", 2_000_000),
+            location(8, "München", "München,Bavaria,Germany", 900_000),
+        ];
+        assert_eq!(best_match("münchen", munichs).unwrap().id, 8);
+    }
+
+    #[test]
+    fn params_for_each_location() {
+        let resp = LocationResp {
+            request_info: RequestInfo { success: true },
+            locations_total: 2,
+            locations_total_current_page: 2,
+            page: 1,
+            limit: 10,
+            locations: vec![
+                location(1, "Boston", "Boston,Massachusetts,United States", 4_000_000),
+                location(2, "Chicago", "Chicago,Illinois,United States", 9_000_000),
+            ],
+        };
+        let params: Vec<Params> = resp.into_params_iter("coffee shops").collect();
+        let locations: Vec<Option<&str>> = params.iter().map(|p| p.location.as_deref()).collect();
+        assert_eq!(locations, vec![Some("Boston,Massachusetts,United States"), Some("Chicago,Illinois,United States")]);
+        assert!(params.iter().all(|p| p.q == "coffee shops"));
+    }
+
+    #[test]
+    fn nearest() {
+        let mut boston = location(1, "Boston", "Boston,Massachusetts,United States", 4_000_000);
+        boston.gps_coordinates = "42.3601,-71.0589".parse().unwrap();
+        let mut chicago = location(2, "Chicago", "Chicago,Illinois,United States", 9_000_000);
+        chicago.gps_coordinates = "41.8781,-87.6298".parse().unwrap();
+        let locations = vec![boston, chicago];
+        let providence = "41.824,-71.4128".parse().unwrap();
+        assert_eq!(nearest_location(&locations, &providence).unwrap().id, 1);
+        assert!(nearest_location(&[], &providence).is_none());
+    }
+
+    #[test]
+    fn url_with_type_and_country_code() {
+        let config = LocReqConfig::builder("boston").api_key("demo").location_type(LocationType::City).country_code("us").build();
+        assert_eq!(config.to_url(), "https://api.scaleserp.com/locations?api_key=demo&q=boston&type=city&country_code=us");
+    }
+
+    #[test]
+    fn builder_urls() {
+        let config = LocReqConfig::builder("boston")
+            .api_key("secret")
+            .location_type(LocationType::City)
+            .country_code("us")
+            .limit(50)
+            .page(2)
+            .build();
+        assert_eq!(config.to_url(), "https://api.scaleserp.com/locations?api_key=secret&q=boston&type=city&country_code=us&limit=50&page=2");
+        assert_eq!(config.redacted_url(), "https://api.scaleserp.com/locations?api_key=***&q=boston&type=city&country_code=us&limit=50&page=2");
+
+        let config = LocReqConfig::builder("München").api_key("demo").location_type(LocationType::PostalCode).build();
+        assert_eq!(config.to_url(), "https://api.scaleserp.com/locations?api_key=demo&q=M%C3%BCnchen&type=postal_code");
+
+        let config = LocReqConfig::builder("St. John's").api_key("demo").build();
+        assert_eq!(config.to_url(), "https://api.scaleserp.com/locations?api_key=demo&q=St.%20John%27s");
+    }
+
+    #[test]
+    fn location_type_field_name() {
+        let json = r#"{
+            "id": 1023191, "name": "New York", "type": "City", "full_name": "New York,New York,United States",
+            "parent_id": 21167, "country_code": "US", "reach": 29000000,
+            "gps_coordinates": {"latitude": 40.7128, "longitude": -74.006}
+        }"#;
+        let location: Location = serde_json::from_str(json).unwrap();
+        assert_eq!(location.r#type, "City");
+        assert_eq!(location.full_name, "New York,New York,United States");
+    }
+
+    #[test]
+    fn merge_overlapping_sets() {
+        let cities = vec![
+            location(1, "Springfield", "Springfield,Illinois,United States", 100_000),
+            location(2, "Springfield", "Springfield,Missouri,United States", 150_000),
+        ];
+        let postal_codes = vec![
+            location(3, "62701", "62701,Illinois,United States", 20_000),
+            location(2, "Springfield", "Springfield,Missouri,United States", 150_000), // also in cities
+            location(4, "65801", "65801,Missouri,United States", 100_000),
+        ];
+        let merged = merge_locations(vec![cities, postal_codes]);
+        let ids: Vec<i32> = merged.iter().map(|location| location.id).collect();
+        assert_eq!(ids, vec![2, 1, 4, 3]); // by reach, ties by id
+        assert!(merge_locations(Vec::new()).is_empty());
+    }
+
+    /// records the type of every locations request and blocks it
+    struct RecordTypes(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl crate::middleware::Middleware for RecordTypes {
+        fn before_request(&self, request: &mut crate::middleware::ApiRequest) -> Result<(), Error> {
+            let location_type = request.url.query_pairs().find(|(key, _)| key == "type").map(|(_, value)| value.to_string());
+            self.0.lock().unwrap().push(location_type.unwrap_or_default());
+            Err(Error::Blocked("recorded".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_types_looks_up_each_type() {
+        let types = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = ScaleSerpClient::builder().api_key("demo").middleware(RecordTypes(types.clone())).build().unwrap();
+        let result = fetch_types(&client, "springfield", &[LocationType::City, LocationType::PostalCode], Some("us")).await;
+        assert!(matches!(result, Err(Error::Blocked(_))));
+        let mut types = types.lock().unwrap().clone();
+        types.sort();
+        assert_eq!(types, vec!["city", "postal_code"]);
+    }
+}
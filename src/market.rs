@@ -0,0 +1,76 @@
+use serde::{Serialize, Deserialize};
+use crate::search::{Params, SearchParameters};
+
+/// The market a search ran in: Google domain, country, language and location
+/// Use it as a HashMap key to group results by market. Every part is lowercased except the location,
+/// and a missing google_domain is ScaleSERP's default of "google.com"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MarketKey {
+    pub google_domain: String,
+    pub gl: Option<String>,
+    pub hl: Option<String>,
+    pub location: Option<String>,
+}
+
+impl MarketKey {
+
+    fn new(google_domain: Option<&str>, gl: Option<&str>, hl: Option<&str>, location: Option<&str>) -> Self {
+        let lowercase = |value: Option<&str>| value.map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty());
+        MarketKey {
+            google_domain: lowercase(google_domain).unwrap_or_else(|| "google.com".to_string()),
+            gl: lowercase(gl),
+            hl: lowercase(hl),
+            location: location.map(|l| l.trim().to_string()).filter(|l| !l.is_empty()),
+        }
+    }
+}
+
+impl From<&Params> for MarketKey {
+    /// the market a search for params is sent to
+    fn from(params: &Params) -> Self {
+        MarketKey::new(params.google_domain.as_deref(), params.gl.as_deref(), params.hl.as_deref(), params.location.as_deref())
+    }
+}
+
+impl From<&SearchParameters> for MarketKey {
+    /// the market ScaleSERP says a response came from
+    fn from(parameters: &SearchParameters) -> Self {
+        MarketKey::new(parameters.google_domain.as_deref(), parameters.gl.as_deref(), parameters.hl.as_deref(), Some(&parameters.location))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::search::Resp;
+
+    #[test]
+    fn market_keys() {
+        let uk = Params::new("secret", "ssd", "London,England,United Kingdom").with_google_domain("Google.co.uk").with_gl("GB").with_hl("en");
+        let us = Params::new("secret", "ssd", "United States").with_gl("us");
+        let key = MarketKey::from(&uk);
+        assert_eq!(key, MarketKey {
+            google_domain: "google.co.uk".to_string(),
+            gl: Some("gb".to_string()),
+            hl: Some("en".to_string()),
+            location: Some("London,England,United Kingdom".to_string()),
+        });
+        assert_eq!(MarketKey::from(&us).google_domain, "google.com");
+
+        let mut grouped: HashMap<MarketKey, Vec<&Params>> = HashMap::new();
+        for params in [&uk, &us, &uk] {
+            grouped.entry(MarketKey::from(params)).or_default().push(params);
+        }
+        assert_eq!(grouped[&key].len(), 2);
+
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+        json["search_parameters"]["google_domain"] = "google.co.uk".into();
+        json["search_parameters"]["gl"] = "gb".into();
+        json["search_parameters"]["hl"] = "en".into();
+        json["search_parameters"]["location"] = "London,England,United Kingdom".into();
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        assert_eq!(MarketKey::from(&resp.search_parameters), key);
+    }
+}
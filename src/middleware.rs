@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use url::Url;
+use crate::error::Error;
+use crate::query;
+
+/// The ScaleSERP endpoint a request is sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Search,
+    Locations,
+    Account,
+}
+
+/// A request the client is about to send
+/// Middleware may change the URL, i.e. to add parameters for a tenant
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub endpoint: Endpoint,
+    pub url: Url,
+}
+
+impl ApiRequest {
+
+    /// the URL with the api_key value replaced by "***", safe to log
+    pub fn redacted_url(&self) -> String {
+        query::redact_api_key(self.url.as_str())
+    }
+}
+
+/// The raw response to an ApiRequest, before it is deserialized
+#[derive(Debug, Clone)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub headers: reqwest::header::HeaderMap,
+    pub body: String,
+}
+
+/// Custom behaviour around every call a ScaleSerpClient makes
+/// Middleware runs in the order it was registered on the builder
+pub trait Middleware: Send + Sync {
+
+    /// called before the request is sent. Returning an error stops the request from being sent
+    fn before_request(&self, _request: &mut ApiRequest) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// called once the request has completed or failed, including when middleware stopped it
+    fn after_response(&self, _request: &ApiRequest, _response: &Result<ApiResponse, Error>) {}
+}
+
+impl<M: Middleware + ?Sized> Middleware for Arc<M> {
+    fn before_request(&self, request: &mut ApiRequest) -> Result<(), Error> {
+        (**self).before_request(request)
+    }
+
+    fn after_response(&self, request: &ApiRequest, response: &Result<ApiResponse, Error>) {
+        (**self).after_response(request, response)
+    }
+}
+
+
+/// Logs every request and its outcome with the log crate, with the API key redacted
+pub struct LoggingMiddleware;
+
+impl Middleware for LoggingMiddleware {
+    fn before_request(&self, request: &mut ApiRequest) -> Result<(), Error> {
+        log::debug!("{:?} request: {}", request.endpoint, request.redacted_url());
+        Ok(())
+    }
+
+    fn after_response(&self, request: &ApiRequest, response: &Result<ApiResponse, Error>) {
+        match response {
+            Ok(response) => log::debug!("{:?} response: status {} ({} bytes)", request.endpoint, response.status, response.body.len()),
+            Err(e) => log::warn!("{:?} request failed: {}", request.endpoint, e),
+        }
+    }
+}
+
+
+/// Stops every request while frozen, i.e. during an incident
+/// Keep an Arc to the switch to freeze and unfreeze it after the client is built
+#[derive(Default)]
+pub struct FreezeSwitch {
+    frozen: AtomicBool,
+}
+
+impl FreezeSwitch {
+
+    pub fn new() -> Self {
+        FreezeSwitch::default()
+    }
+
+    pub fn freeze(&self) {
+        self.frozen.store(true, Ordering::SeqCst);
+    }
+
+    pub fn unfreeze(&self) {
+        self.frozen.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::SeqCst)
+    }
+}
+
+impl Middleware for FreezeSwitch {
+    fn before_request(&self, _request: &mut ApiRequest) -> Result<(), Error> {
+        if self.is_frozen() {
+            return Err(Error::Blocked("requests are frozen".to_string()));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::client::ScaleSerpClient;
+    use crate::search::Params;
+
+    /// records what it sees and adds a tenant parameter
+    struct Recorder {
+        name: &'static str,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn before_request(&self, request: &mut ApiRequest) -> Result<(), Error> {
+            self.seen.lock().unwrap().push(format!("{} before", self.name));
+            request.url.query_pairs_mut().append_pair("tenant", self.name);
+            Ok(())
+        }
+
+        fn after_response(&self, request: &ApiRequest, response: &Result<ApiResponse, Error>) {
+            let tenants: Vec<String> = request.url.query_pairs()
+                .filter(|(key, _)| key == "tenant")
+                .map(|(_, value)| value.into_owned())
+                .collect();
+            let outcome = match response {
+                Ok(_) => "ok".to_string(),
+                Err(e) => e.to_string(),
+            };
+            self.seen.lock().unwrap().push(format!("{} after {:?}: {}", self.name, tenants, outcome));
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_runs_in_order_and_freeze_blocks() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let freeze = Arc::new(FreezeSwitch::new());
+        freeze.freeze();
+        let client = ScaleSerpClient::builder()
+            .api_key("demo")
+            .middleware(Recorder { name: "a", seen: seen.clone() })
+            .middleware(Recorder { name: "b", seen: seen.clone() })
+            .middleware(freeze.clone())
+            .middleware(LoggingMiddleware)
+            .build()
+            .unwrap();
+        let result = client.search(&Params::new_env_usa("external ssd")).await;
+        assert!(matches!(result, Err(Error::Blocked(_))));
+        assert_eq!(*seen.lock().unwrap(), vec![
+            "a before",
+            "b before",
+            "a after [\"a\", \"b\"]: request blocked: requests are frozen",
+            "b after [\"a\", \"b\"]: request blocked: requests are frozen",
+        ]);
+        let result = client.check_connectivity().await;
+        assert!(matches!(result, Err(Error::Blocked(_))));
+    }
+
+    #[test]
+    fn redacted_url() {
+        let request = ApiRequest {
+            endpoint: Endpoint::Search,
+            url: Url::parse("https://api.scaleserp.com/search?api_key=secret&q=external+ssd").unwrap(),
+        };
+        assert_eq!(request.redacted_url(), "https://api.scaleserp.com/search?api_key=***&q=external+ssd");
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::Error;
+
+/// One piece of a query built with QueryBuilder
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Term(String),
+    Phrase(String),
+    Operator(&'static str, String), // i.e. ("intitle", "pricing")
+    Exclude(String),
+    AnyOf(Vec<String>),
+}
+
+/// Composes search terms and operators into a correctly quoted query
+/// i.e. `QueryBuilder::new().site("example.com").intitle("pricing").exclude("login")` builds `site:example.com intitle:pricing -login`
+/// Google has no way to escape a quote inside a quoted phrase, so build returns Error::InvalidQuery for phrases containing one
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryBuilder {
+    parts: Vec<Part>,
+}
+
+impl QueryBuilder {
+
+    pub fn new() -> Self {
+        QueryBuilder::default()
+    }
+
+    /// plain search words. Any quotes are dropped so they can not open a phrase by accident
+    pub fn term(mut self, term: &str) -> Self {
+        self.parts.push(Part::Term(term.to_string()));
+        self
+    }
+
+    /// words that must appear exactly in this order, i.e. `"external ssd"`
+    pub fn phrase(mut self, phrase: &str) -> Self {
+        self.parts.push(Part::Phrase(phrase.to_string()));
+        self
+    }
+
+    /// only results from domain or its subdomains, i.e. `site:example.com`
+    pub fn site(mut self, domain: &str) -> Self {
+        self.parts.push(Part::Operator("site", domain.to_string()));
+        self
+    }
+
+    /// only results with text in their title, quoted if it is more than one word
+    pub fn intitle(mut self, text: &str) -> Self {
+        self.parts.push(Part::Operator("intitle", text.to_string()));
+        self
+    }
+
+    /// only results with text in their URL, quoted if it is more than one word
+    pub fn inurl(mut self, text: &str) -> Self {
+        self.parts.push(Part::Operator("inurl", text.to_string()));
+        self
+    }
+
+    /// only files of this type, i.e. "pdf"
+    pub fn filetype(mut self, extension: &str) -> Self {
+        self.parts.push(Part::Operator("filetype", extension.trim_start_matches('.').to_string()));
+        self
+    }
+
+    /// leave out results containing term, i.e. `-login` or `-"free trial"`
+    pub fn exclude(mut self, term: &str) -> Self {
+        self.parts.push(Part::Exclude(term.to_string()));
+        self
+    }
+
+    /// results containing at least one of alternatives, i.e. `(ssd OR "solid state drive")`
+    pub fn any_of(mut self, alternatives: &[&str]) -> Self {
+        self.parts.push(Part::AnyOf(alternatives.iter().map(|a| a.to_string()).collect()));
+        self
+    }
+
+    /// the query string, or Error::InvalidQuery if a part can not be quoted or the query is empty
+    pub fn build(&self) -> Result<String, Error> {
+        let mut pieces = Vec::with_capacity(self.parts.len());
+        for part in &self.parts {
+            let piece = match part {
+                Part::Term(term) => words(&term.replace('"', "")),
+                Part::Phrase(phrase) => quoted(phrase)?,
+                Part::Operator(operator, value) if matches!(*operator, "site" | "filetype") => {
+                    let value = value.trim();
+                    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+                        return Err(Error::InvalidQuery(format!("{}: needs a single word, not {:?}", operator, value)));
+                    }
+                    format!("{}:{}", operator, value)
+                },
+                Part::Operator(operator, value) => format!("{}:{}", operator, word_or_phrase(value)?),
+                Part::Exclude(term) => format!("-{}", word_or_phrase(term)?),
+                Part::AnyOf(alternatives) => {
+                    let alternatives = alternatives.iter()
+                        .filter(|a| !a.trim().is_empty())
+                        .map(|a| word_or_phrase(a))
+                        .collect::<Result<Vec<String>, Error>>()?;
+                    match alternatives.len() {
+                        0 => String::new(),
+                        1 => alternatives[0].clone(),
+                        _ => format!("({})", alternatives.join(" OR ")),
+                    }
+                },
+            };
+            if !piece.is_empty() {
+                pieces.push(piece);
+            }
+        }
+        if pieces.is_empty() {
+            return Err(Error::InvalidQuery("the query is empty".to_string()));
+        }
+        Ok(pieces.join(" "))
+    }
+}
+
+/// a search for terms on domain and its subdomains, i.e. `site:example.com pricing plans`
+pub fn site_search(domain: &str, terms: &str) -> QueryBuilder {
+    QueryBuilder::new().site(domain).term(terms)
+}
+
+/// text with its whitespace collapsed to single spaces
+fn words(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// text in quotes, or Error::InvalidQuery if it is empty or contains a quote itself
+fn quoted(text: &str) -> Result<String, Error> {
+    let text = words(text);
+    if text.is_empty() {
+        return Err(Error::InvalidQuery("an exact phrase is empty".to_string()));
+    }
+    if text.contains('"') {
+        return Err(Error::InvalidQuery(format!("quotes can not be nested in {:?}", text)));
+    }
+    Ok(format!("\"{}\"", text))
+}
+
+/// a single word as it is, anything longer in quotes
+fn word_or_phrase(text: &str) -> Result<String, Error> {
+    let text = words(text);
+    if text.contains(' ') || text.contains('"') || text.is_empty() {
+        quoted(&text)
+    } else {
+        Ok(text)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(query: QueryBuilder) -> String {
+        match query.build() {
+            Err(Error::InvalidQuery(reason)) => reason,
+            other => panic!("expected InvalidQuery, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn builds_exact_query_strings() {
+        let cases = vec![
+            (QueryBuilder::new().term("external ssd"), "external ssd"),
+            (QueryBuilder::new().term("  external \t ssd "), "external ssd"),
+            (QueryBuilder::new().term("5\" drive"), "5 drive"),
+            (QueryBuilder::new().phrase("external ssd"), "\"external ssd\""),
+            (QueryBuilder::new().phrase(" external  ssd "), "\"external ssd\""),
+            (QueryBuilder::new().site("example.com"), "site:example.com"),
+            (QueryBuilder::new().intitle("pricing"), "intitle:pricing"),
+            (QueryBuilder::new().intitle("pricing page"), "intitle:\"pricing page\""),
+            (QueryBuilder::new().inurl("blog"), "inurl:blog"),
+            (QueryBuilder::new().inurl("blog posts"), "inurl:\"blog posts\""),
+            (QueryBuilder::new().filetype("pdf"), "filetype:pdf"),
+            (QueryBuilder::new().filetype(".pdf"), "filetype:pdf"),
+            (QueryBuilder::new().exclude("login"), "-login"),
+            (QueryBuilder::new().exclude("free trial"), "-\"free trial\""),
+            (QueryBuilder::new().any_of(&["ssd", "solid state drive"]), "(ssd OR \"solid state drive\")"),
+            (QueryBuilder::new().any_of(&["ssd"]), "ssd"),
+            (QueryBuilder::new().any_of(&[]).term("ssd"), "ssd"),
+            (QueryBuilder::new().any_of(&["ssd", " "]).term("review"), "ssd review"),
+            (
+                QueryBuilder::new().site("example.com").intitle("pricing").exclude("login"),
+                "site:example.com intitle:pricing -login",
+            ),
+            (
+                QueryBuilder::new().term("backup").phrase("external ssd").any_of(&["mac", "windows"]).filetype("pdf").exclude("ads"),
+                "backup \"external ssd\" (mac OR windows) filetype:pdf -ads",
+            ),
+            (site_search("example.com", "pricing plans"), "site:example.com pricing plans"),
+            (site_search("example.com", ""), "site:example.com"),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(query.build().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_what_google_can_not_parse() {
+        assert_eq!(invalid(QueryBuilder::new()), "the query is empty");
+        assert_eq!(invalid(QueryBuilder::new().term(" \" ")), "the query is empty");
+        assert_eq!(invalid(QueryBuilder::new().phrase("")), "an exact phrase is empty");
+        assert_eq!(invalid(QueryBuilder::new().phrase("the \"best\" ssd")), "quotes can not be nested in \"the \\\"best\\\" ssd\"");
+        assert!(invalid(QueryBuilder::new().intitle("\"pricing\"")).starts_with("quotes can not be nested"));
+        assert!(invalid(QueryBuilder::new().exclude("a\"b")).starts_with("quotes can not be nested"));
+        assert!(invalid(QueryBuilder::new().any_of(&["a", "\"b c\""])).starts_with("quotes can not be nested"));
+        assert_eq!(invalid(QueryBuilder::new().site("example .com")), "site: needs a single word, not \"example .com\"");
+        assert_eq!(invalid(QueryBuilder::new().site("")), "site: needs a single word, not \"\"");
+        assert!(invalid(QueryBuilder::new().filetype("p\"df")).starts_with("filetype:"));
+    }
+}
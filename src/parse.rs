@@ -0,0 +1,143 @@
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Serialize, Deserialize};
+use crate::search::Resp;
+
+/// Options for Resp::from_json_with
+/// The caps are applied while parsing: results past a cap are skipped over without being allocated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct ParseOptions {
+    pub max_organic_results: Option<usize>,
+    pub max_related_questions: Option<usize>,
+    pub max_top_stories: Option<usize>,
+}
+
+thread_local! {
+    /// the options of the from_json_with call running on this thread, read by the capped fields of RawResp
+    static OPTIONS: Cell<ParseOptions> = Cell::new(ParseOptions::default());
+}
+
+/// puts the previous options back when parsing ends, even if it fails
+struct RestoreOptions(ParseOptions);
+
+impl Drop for RestoreOptions {
+    fn drop(&mut self) {
+        OPTIONS.with(|options| options.set(self.0));
+    }
+}
+
+impl Resp {
+
+    /// parse a response, keeping at most as many organic results, related questions and top stories as options allow
+    /// Use this instead of serde_json::from_str when only the top results matter, i.e. for 100-result pages
+    pub fn from_json_with(json: &str, options: &ParseOptions) -> Result<Resp, serde_json::Error> {
+        let _restore = RestoreOptions(OPTIONS.with(|current| current.replace(*options)));
+        serde_json::from_str(json)
+    }
+}
+
+/// Deserializes a sequence, keeping its first cap elements and skipping the rest
+struct Capped<T> {
+    cap: Option<usize>,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for Capped<T> {
+    type Value = Vec<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<T>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Capped<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+        let cap = self.cap.unwrap_or(usize::MAX);
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(cap));
+        while items.len() < cap {
+            match seq.next_element()? {
+                Some(item) => items.push(item),
+                None => return Ok(items),
+            }
+        }
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(items)
+    }
+}
+
+/// Deserializes an optional sequence with Capped
+struct CappedOption<T>(Capped<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for CappedOption<T> {
+    type Value = Option<Vec<T>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0.deserialize(deserializer).map(Some)
+    }
+}
+
+fn capped<T>(cap: impl Fn(&ParseOptions) -> Option<usize>) -> Capped<T> {
+    Capped { cap: OPTIONS.with(|options| cap(&options.get())), marker: PhantomData }
+}
+
+pub(crate) fn organic_results<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    capped(|options| options.max_organic_results).deserialize(deserializer)
+}
+
+pub(crate) fn related_questions<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<Vec<T>>, D::Error> {
+    deserializer.deserialize_option(CappedOption(capped(|options| options.max_related_questions)))
+}
+
+pub(crate) fn top_stories<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<Vec<T>>, D::Error> {
+    deserializer.deserialize_option(CappedOption(capped(|options| options.max_top_stories)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_while_parsing() {
+        // the external ssd fixture with its first organic result repeated at positions 1 to 100
+        let json = include_str!("../fixtures/search_hundred_results.json");
+        let options = ParseOptions { max_organic_results: Some(10), max_related_questions: Some(1), max_top_stories: Some(0) };
+        let resp = Resp::from_json_with(json, &options).unwrap();
+        assert_eq!(resp.organic_results.len(), 10);
+        assert_eq!(resp.organic_results.last().unwrap().position, 10);
+        assert_eq!(resp.related_questions.len(), 1);
+        assert!(resp.top_stories.is_empty() && resp.had_top_stories_block());
+
+        // the options only apply to that call
+        let resp: Resp = serde_json::from_str(json).unwrap();
+        assert_eq!(resp.organic_results.len(), 100);
+        assert_eq!(Resp::from_json_with(json, &ParseOptions::default()).unwrap().organic_results.len(), 100);
+
+        let mut missing: serde_json::Value = serde_json::from_str(json).unwrap();
+        missing["top_stories"] = serde_json::Value::Null;
+        missing.as_object_mut().unwrap().remove("related_questions");
+        let resp = Resp::from_json_with(&missing.to_string(), &options).unwrap();
+        assert!(!resp.had_top_stories_block() && !resp.had_related_questions_block());
+    }
+}
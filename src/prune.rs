@@ -0,0 +1,127 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::schema::REQUIRED_BLOCKS;
+use crate::search::Resp;
+
+/// What to drop or shorten in a response before storing it, see Resp::pruned
+/// It is serializable so it can live in a config file. The default keeps everything
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct PruneSpec {
+    pub max_snippet_chars: Option<usize>, // organic snippets are cut to this many characters
+    pub max_organic_position: Option<usize>, // organic results past this position are dropped
+    pub drop_product_specifications: bool, // empty top_products[].specifications
+    pub drop_blocks: Vec<String>, // top-level blocks to drop, i.e. "top_products". The four required blocks are never dropped
+}
+
+/// How much pruning shrank a response, measured as serialized JSON
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PruneStats {
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+impl PruneStats {
+
+    pub fn bytes_saved(&self) -> usize {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+impl PruneSpec {
+
+    /// apply the spec to a serialized response, only ever removing optional data
+    fn apply(&self, value: &mut Value) {
+        let Some(blocks) = value.as_object_mut() else { return };
+        for block in &self.drop_blocks {
+            if !REQUIRED_BLOCKS.contains(&block.as_str()) {
+                blocks.remove(block);
+            }
+        }
+        if let Some(Value::Array(results)) = blocks.get_mut("organic_results") {
+            if let Some(max_position) = self.max_organic_position {
+                results.retain(|result| result["position"].as_u64().is_some_and(|p| p as usize <= max_position));
+            }
+            if let Some(max_chars) = self.max_snippet_chars {
+                for result in results.iter_mut() {
+                    if let Some(Value::String(snippet)) = result.get_mut("snippet") {
+                        if let Some((cut, _)) = snippet.char_indices().nth(max_chars) {
+                            snippet.truncate(cut);
+                        }
+                    }
+                }
+            }
+        }
+        if self.drop_product_specifications {
+            if let Some(Value::Array(products)) = blocks.get_mut("top_products") {
+                for product in products.iter_mut() {
+                    if let Some(specifications) = product.get_mut("specifications") {
+                        *specifications = Value::Array(Vec::new());
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Resp {
+
+    /// a copy of the response with what spec drops removed, i.e. before archiving it
+    pub fn pruned(&self, spec: &PruneSpec) -> Resp {
+        self.pruned_with_stats(spec).0
+    }
+
+    /// like pruned, also returning how many bytes of JSON pruning saved
+    /// Dropped blocks read as missing afterwards, i.e. had_top_products_block is false
+    pub fn pruned_with_stats(&self, spec: &PruneSpec) -> (Resp, PruneStats) {
+        let mut value = Value::from(self);
+        let bytes_before = value.to_string().len();
+        spec.apply(&mut value);
+        let bytes_after = value.to_string().len();
+        let pruned = serde_json::from_value(value).expect("pruning only removes optional data");
+        (pruned, PruneStats { bytes_before, bytes_after })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Resp {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn default_keeps_everything() {
+        let resp = fixture();
+        let (pruned, stats) = resp.pruned_with_stats(&PruneSpec::default());
+        assert_eq!(stats.bytes_saved(), 0);
+        assert_eq!(serde_json::to_value(&pruned).unwrap(), serde_json::to_value(&resp).unwrap());
+    }
+
+    #[test]
+    fn prune() {
+        let resp = fixture();
+        let spec: PruneSpec = serde_json::from_str(r#"{
+            "max_snippet_chars": 20,
+            "max_organic_position": 3,
+            "drop_product_specifications": true,
+            "drop_blocks": ["top_stories", "related_searches", "search_metadata"]
+        }"#).unwrap();
+        let (pruned, stats) = resp.pruned_with_stats(&spec);
+        assert!(stats.bytes_saved() > 0);
+        assert_eq!(stats.bytes_before - stats.bytes_saved(), stats.bytes_after);
+
+        assert_eq!(pruned.organic_results.len(), 3);
+        assert!(pruned.organic_results.iter().all(|result| result.snippet.chars().count() <= 20));
+        assert_eq!(pruned.organic_results[0].snippet, resp.organic_results[0].snippet.chars().take(20).collect::<String>());
+        assert!(pruned.top_stories.is_empty() && !pruned.had_top_stories_block());
+        assert!(pruned.related_searches.is_empty());
+        assert!(pruned.top_products.iter().all(|product| product.specifications.is_empty()));
+        assert_eq!(pruned.top_products.len(), resp.top_products.len());
+        // required blocks are kept even when asked to drop them
+        assert_eq!(pruned.search_metadata.created_at, resp.search_metadata.created_at);
+        assert_eq!(pruned.ads.len(), resp.ads.len());
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+
+/// characters that would end or corrupt a query parameter value
+/// '+' is encoded too: the server decodes a raw '+' as a space, which would lose it from queries like "c++"
+const QUERY_VALUE: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'&').add(b'\'').add(b'+').add(b'<').add(b'=').add(b'>')
+    .add(b'?').add(b'[').add(b']').add(b'\\').add(b'^').add(b'`').add(b'{').add(b'|').add(b'}');
+
+/// percent-encode value for use as a query parameter value, i.e. "M%C3%BCnchen" for "München"
+pub(crate) fn encode(value: &str) -> String {
+    utf8_percent_encode(value, QUERY_VALUE).to_string()
+}
+
+/// append "&key=value" to url, encoding value
+pub(crate) fn push_param(url: &mut String, key: &str, value: &str) {
+    url.push('&');
+    url.push_str(key);
+    url.push('=');
+    url.push_str(&encode(value));
+}
+
+/// base followed by the encoded pairs as its query string
+pub(crate) fn url_with_pairs(base: &str, pairs: &[(&str, String)]) -> String {
+    let query: Vec<String> = pairs.iter().map(|(key, value)| format!("{}={}", key, encode(value))).collect();
+    format!("{}?{}", base, query.join("&"))
+}
+
+/// a listing of a request: "GET base" and then one "  key = value" line per pair, with the API key masked
+pub(crate) fn write_listing(f: &mut impl fmt::Write, base: &str, pairs: &[(&str, String)]) -> fmt::Result {
+    write!(f, "GET {}", base)?;
+    for (key, value) in pairs {
+        let value = if *key == "api_key" { "***" } else { value.as_str() };
+        write!(f, "\n  {} = {}", key, value)?;
+    }
+    Ok(())
+}
+
+/// url with the value of its api_key parameter replaced by "***", safe to log
+pub(crate) fn redact_api_key(url: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some(split) => split,
+        None => return url.to_string(),
+    };
+    let pairs: Vec<&str> = query.split('&')
+        .map(|pair| if pair.starts_with("api_key=") { "api_key=***" } else { pair })
+        .collect();
+    format!("{}?{}", base, pairs.join("&"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(encode("München"), "M%C3%BCnchen");
+        assert_eq!(encode("rock & roll #1"), "rock%20%26%20roll%20%231");
+        assert_eq!(encode("New York,New York,United States"), "New%20York,New%20York,United%20States");
+        assert_eq!(encode("c++ 2+2"), "c%2B%2B%202%2B2");
+    }
+
+    #[test]
+    fn redaction() {
+        assert_eq!(redact_api_key("https://api.scaleserp.com/search?api_key=secret&q=ssd"), "https://api.scaleserp.com/search?api_key=***&q=ssd");
+        assert_eq!(redact_api_key("https://api.scaleserp.com/account"), "https://api.scaleserp.com/account");
+    }
+}
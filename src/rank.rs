@@ -0,0 +1,92 @@
+use serde::{Serialize, Deserialize};
+use crate::features::SerpFeatures;
+use crate::link::{domain_matches, normalize_domain};
+use crate::search::Resp;
+
+/// Where one domain ranked for one search, as a row for a rank history table
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RankObservation {
+    pub keyword: String,
+    pub location: String,
+    pub device: Option<String>, // None if the response does not say, which means desktop
+    pub domain: String, // normalized, see normalize_domain
+    pub position: Option<usize>, // the best organic position, None if the domain does not rank organically
+    pub appearances: usize, // how many organic results are on the domain
+    #[serde(default)]
+    pub in_ads: bool, // true if one of the ads is on the domain, whether or not it ranks organically
+    pub serp_features: SerpFeatures,
+    pub fetched_at: String, // when ScaleSERP ran the search, i.e. '2021-07-30T15:00:37.000Z'
+}
+
+impl RankObservation {
+
+    /// where target_domain (or its subdomains) ranked in resp
+    pub fn from_resp(resp: &Resp, target_domain: &str) -> Self {
+        RankObservation::for_domains(resp, &[target_domain]).remove(0)
+    }
+
+    /// where each of domains ranked in resp, in the same order, in a single pass over the organic results
+    pub fn for_domains(resp: &Resp, domains: &[&str]) -> Vec<Self> {
+        let mut tallies: Vec<(Option<usize>, usize)> = vec![(None, 0); domains.len()];
+        for result in &resp.organic_results {
+            for (domain, (position, appearances)) in domains.iter().zip(tallies.iter_mut()) {
+                if domain_matches(&result.domain, domain) {
+                    *position = Some(position.map_or(result.position, |best| best.min(result.position)));
+                    *appearances += 1;
+                }
+            }
+        }
+        let serp_features = SerpFeatures::of(resp);
+        domains.iter().zip(tallies).map(|(domain, (position, appearances))| RankObservation {
+            in_ads: resp.ads.iter().any(|ad| domain_matches(&ad.domain, domain)),
+            keyword: resp.search_parameters.q.clone(),
+            location: resp.search_parameters.location.clone(),
+            device: resp.search_parameters.device.clone(),
+            domain: normalize_domain(domain),
+            position,
+            appearances,
+            serp_features,
+            fetched_at: resp.search_metadata.created_at.clone(),
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Resp {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn observations() {
+        let resp = fixture();
+        // samsung.com ranks twice, with and without "www."
+        let samsung = RankObservation::from_resp(&resp, "www.Samsung.com");
+        assert_eq!(samsung.domain, "samsung.com");
+        assert_eq!(samsung.position, Some(2));
+        assert_eq!(samsung.appearances, 2);
+        assert_eq!(samsung.keyword, "external ssd");
+        assert_eq!(samsung.fetched_at, resp.search_metadata.created_at);
+        assert!(samsung.serp_features.contains(SerpFeatures::ADS));
+
+        // bestbuy.com only has an ad
+        let bestbuy = RankObservation::from_resp(&resp, "bestbuy.com");
+        assert_eq!((bestbuy.position, bestbuy.appearances, bestbuy.in_ads), (None, 0, true));
+        // samsung.com also has an ad, pcmag.com only ranks organically
+        assert!(samsung.in_ads);
+        assert!(!RankObservation::from_resp(&resp, "pcmag.com").in_ads);
+
+        let absent = RankObservation::from_resp(&resp, "example.com");
+        assert_eq!((absent.position, absent.in_ads), (None, false));
+
+        let many = RankObservation::for_domains(&resp, &["tomshardware.com", "example.com", "pcmag.com"]);
+        let positions: Vec<Option<usize>> = many.iter().map(|observation| observation.position).collect();
+        assert_eq!(positions, vec![Some(3), None, Some(1)]);
+
+        let json = serde_json::to_string(&samsung).unwrap();
+        assert_eq!(serde_json::from_str::<RankObservation>(&json).unwrap(), samsung);
+    }
+}
@@ -0,0 +1,141 @@
+use serde::Serialize;
+use crate::link::domain_matches;
+use crate::search::Resp;
+
+/// How one domain shows up across a set of searches
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    pub domain: String,
+    pub queries: Vec<QueryCoverage>,
+}
+
+/// How one domain shows up in the response to one query
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QueryCoverage {
+    pub query: String,
+    pub location: String,
+    pub best_position: Option<usize>, // the best organic position, None if the domain does not rank
+    pub organic: bool,
+    pub ads: bool,
+    pub top_stories: bool,
+    pub related_questions: bool, // the domain is the source of a "People also ask" answer
+}
+
+impl QueryCoverage {
+
+    /// true if the domain appears anywhere in the response
+    pub fn appears(&self) -> bool {
+        self.organic || self.ads || self.top_stories || self.related_questions
+    }
+}
+
+impl CoverageReport {
+
+    /// the queries the domain ranks for organically
+    pub fn ranking_queries(&self) -> Vec<&QueryCoverage> {
+        self.queries.iter().filter(|coverage| coverage.organic).collect()
+    }
+
+    /// the report as CSV with a header row, one row per query
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("domain,query,location,best_position,organic,ads,top_stories,related_questions\n");
+        for coverage in &self.queries {
+            let best_position = coverage.best_position.map(|p| p.to_string()).unwrap_or_default();
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                csv_field(&self.domain),
+                csv_field(&coverage.query),
+                csv_field(&coverage.location),
+                best_position,
+                coverage.organic,
+                coverage.ads,
+                coverage.top_stories,
+                coverage.related_questions,
+            ));
+        }
+        csv
+    }
+
+    /// the report as CSV starting with a UTF-8 byte order mark
+    /// Excel reads CSV files without one in the local code page, which garbles non-Latin queries and locations
+    pub fn to_excel_csv(&self) -> String {
+        format!("\u{feff}{}", self.to_csv())
+    }
+}
+
+/// quote a CSV field if it contains a separator, quote or line break
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Report where domain (or its subdomains) appears across responses: one entry per response, in the same order
+pub fn domain_coverage(responses: &[Resp], domain: &str) -> CoverageReport {
+    let queries = responses.iter().map(|resp| {
+        let best_position = resp.organic_results.iter()
+            .filter(|result| domain_matches(&result.domain, domain))
+            .map(|result| result.position)
+            .min();
+        QueryCoverage {
+            query: resp.search_parameters.q.clone(),
+            location: resp.search_parameters.location.clone(),
+            best_position,
+            organic: best_position.is_some(),
+            ads: resp.ads.iter().any(|ad| domain_matches(&ad.domain, domain)),
+            top_stories: resp.top_stories.iter().any(|story| story.link.is_on_domain(domain)),
+            related_questions: resp.related_questions.iter().any(|question| question.source.link.is_on_domain(domain)),
+        }
+    }).collect();
+    CoverageReport { domain: domain.to_string(), queries }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Resp {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn coverage() {
+        let mut second = fixture();
+        second.search_parameters.q = "ssd, portable".to_string();
+        second.organic_results.retain(|result| result.position > 1);
+        let responses = vec![fixture(), second];
+
+        let report = domain_coverage(&responses, "pcmag.com");
+        assert_eq!(report.queries[0].best_position, Some(1));
+        assert!(report.queries[0].top_stories && report.queries[0].related_questions);
+        assert!(!report.queries[0].ads);
+        assert_eq!(report.queries[1].best_position, None);
+        assert!(report.queries[1].appears()); // still a top story source
+        assert_eq!(report.ranking_queries().len(), 1);
+
+        let samsung = domain_coverage(&responses, "samsung.com");
+        assert_eq!(samsung.queries[0].best_position, Some(2));
+        assert!(samsung.queries[0].ads);
+
+        assert_eq!(report.to_csv(), "\
+domain,query,location,best_position,organic,ads,top_stories,related_questions
+pcmag.com,external ssd,\"New York,New York,United States\",1,true,false,true,true
+pcmag.com,\"ssd, portable\",\"New York,New York,United States\",,false,false,true,true
+");
+    }
+
+    #[test]
+    fn non_latin_csv() {
+        let mut resp = fixture();
+        resp.search_parameters.q = "قرص صلب خارجي".to_string();
+        resp.search_parameters.location = "東京, 日本".to_string();
+        let report = domain_coverage(&[resp], "pcmag.com");
+        let csv = report.to_excel_csv();
+        assert!(csv.starts_with('\u{feff}'));
+        assert_eq!(csv.trim_start_matches('\u{feff}'), report.to_csv());
+        assert!(csv.contains("pcmag.com,قرص صلب خارجي,\"東京, 日本\",1,"));
+    }
+}
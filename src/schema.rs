@@ -0,0 +1,55 @@
+/// the top-level keys of a search response that Resp has typed fields for
+const SUPPORTED_BLOCKS: [&str; 18] = [
+    "request_info",
+    "search_metadata",
+    "search_parameters",
+    "search_information",
+    "ads",
+    "top_stories",
+    "top_products",
+    "related_searches",
+    "related_questions",
+    "organic_results",
+    "app_results",
+    "dictionary_result",
+    "weather_result",
+    "conversion_result",
+    "calculator_result",
+    "hotel_results",
+    "sports_results",
+    "finance_result",
+];
+
+/// the blocks every response has and Resp can not be parsed without. Every other supported block is optional:
+/// lenient parsing may drop it and PruneSpec::drop_blocks may remove it
+pub(crate) const REQUIRED_BLOCKS: [&str; 4] = ["request_info", "search_metadata", "search_parameters", "search_information"];
+
+/// The top-level blocks of a search response this crate models
+/// Compare with the keys of a raw payload (see Resp::unmodeled_blocks) to see what is lost when deserializing
+pub fn supported_blocks() -> &'static [&'static str] {
+    &SUPPORTED_BLOCKS
+}
+
+/// true if Resp models the top-level key
+pub fn is_supported_block(key: &str) -> bool {
+    SUPPORTED_BLOCKS.contains(&key)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Resp;
+
+    #[test]
+    fn supported_blocks_match_resp() {
+        // every field Resp serializes is a block it models, so the list cannot drift from the struct
+        let resp: Resp = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+        let value = serde_json::to_value(&resp).unwrap();
+        let mut fields: Vec<&str> = value.as_object().unwrap().keys().map(|key| key.as_str()).collect();
+        let mut supported = supported_blocks().to_vec();
+        fields.sort();
+        supported.sort();
+        assert_eq!(fields, supported);
+    }
+}
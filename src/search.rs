@@ -0,0 +1,2656 @@
+use std::{env, fmt};
+use std::vec::Vec;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+use serde::{Serialize, Deserialize};
+use crate::error::Error;
+use crate::features::SerpFeatures;
+use crate::authority::PageAuthority;
+use crate::language::LanguageDetector;
+use crate::operators::QueryBuilder;
+use crate::link::{ResultLink, domain_matches, normalize_domain};
+use crate::parse;
+use crate::query;
+use crate::schema;
+pub use crate::vertical::SearchType;
+
+/// the endpoint for regular, synchronous searches
+const SEARCH_URL: &str = "https://api.scaleserp.com/search";
+/// the number of results per page Google returns when num is not set
+pub const DEFAULT_RESULTS_PER_PAGE: usize = 10;
+/// Google stops paginating at around this many results, however many it claims to have
+pub const MAX_PAGINATED_RESULTS: usize = 100;
+/// the endpoint that queues a search and returns a request_id to collect the result from later
+const ASYNC_SEARCH_URL: &str = "https://api.scaleserp.com/search/async";
+
+#[derive(Clone)]
+pub struct Params {
+    // The paramaters for making a call to ScaleSERP
+    pub api_key: String, // your API key
+    pub location: Option<String>, // "United States" etc. None for a search that is not geotargeted
+    pub location_auto: Option<bool>, // false stops ScaleSERP inferring a location, None for its default
+    pub q: String, // The query. Spaces are okay
+    pub async_mode: bool, // queue the search with the asynchronous API instead of waiting for the result
+    pub search_type: Option<SearchType>, // None for a regular web search
+    pub skip_on_incident: Option<SkipOnIncident>, // None to search even while Google has an incident
+    pub num: Option<usize>, // results per page, None for Google's default of 10
+    pub device: Option<Device>, // None for desktop
+    pub hl: Option<String>, // the interface language, i.e. "en"
+    pub gl: Option<String>, // the country to search from, i.e. "us"
+    pub google_domain: Option<String>, // i.e. "google.co.uk", None for ScaleSERP's default of "google.com"
+    pub cr: Option<String>, // only return pages from these countries, i.e. "countryUS" or "countryUS|countryCA"
+    pub as_sitesearch: Option<String>, // only return pages from this site, like "site:" in the query
+    pub safe: Option<bool>, // SafeSearch on or off, None for Google's default
+    pub filter: Option<DuplicateFilter>, // None for Google's default, which omits very similar results
+    pub tag: Option<String>, // a label for attributing credits, i.e. a project name. Never sent to ScaleSERP
+}
+
+/// Whether Google leaves out results that are very similar to ones already shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicateFilter {
+    Enabled,
+    Disabled, // show omitted results too, i.e. to see the real total
+}
+
+impl DuplicateFilter {
+
+    /// the value of the filter parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateFilter::Enabled => "1",
+            DuplicateFilter::Disabled => "0",
+        }
+    }
+}
+
+/// Countries results can be restricted to with Params::with_cr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CountryRestrict {
+    UnitedStates,
+    UnitedKingdom,
+    Canada,
+    Australia,
+    Germany,
+    France,
+    Spain,
+    Italy,
+    Netherlands,
+    India,
+    Japan,
+    Brazil,
+    Mexico,
+}
+
+impl CountryRestrict {
+
+    /// the value of the cr parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CountryRestrict::UnitedStates => "countryUS",
+            CountryRestrict::UnitedKingdom => "countryGB",
+            CountryRestrict::Canada => "countryCA",
+            CountryRestrict::Australia => "countryAU",
+            CountryRestrict::Germany => "countryDE",
+            CountryRestrict::France => "countryFR",
+            CountryRestrict::Spain => "countryES",
+            CountryRestrict::Italy => "countryIT",
+            CountryRestrict::Netherlands => "countryNL",
+            CountryRestrict::India => "countryIN",
+            CountryRestrict::Japan => "countryJP",
+            CountryRestrict::Brazil => "countryBR",
+            CountryRestrict::Mexico => "countryMX",
+        }
+    }
+}
+
+/// The kind of device to search from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Device {
+    Desktop,
+    Mobile,
+    Tablet,
+}
+
+impl Device {
+
+    /// the value of the device parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Device::Desktop => "desktop",
+            Device::Mobile => "mobile",
+            Device::Tablet => "tablet",
+        }
+    }
+
+    /// the device for a device parameter value, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "desktop" => Some(Device::Desktop),
+            "mobile" => Some(Device::Mobile),
+            "tablet" => Some(Device::Tablet),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Params {
+    /// the redacted URL, so the API key never ends up in debug output either
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Params").field(&self.redacted_url()).finish()
+    }
+}
+
+impl fmt::Display for Params {
+    /// the endpoint and then every parameter on its own line, with the API key masked, i.e.
+    /// "GET https://api.scaleserp.com/search\n  api_key = ***\n  q = external ssd"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        query::write_listing(f, self.endpoint(), &self.effective_pairs())
+    }
+}
+
+/// Which Google incidents ScaleSERP should skip searches for, instead of charging for unusual results
+/// Skipped searches come back as SearchOutcome::SkippedIncident
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipOnIncident {
+    AllIncidents, // any incident ScaleSERP reports
+    ParsingIncidents, // only incidents where Google's layout can not be parsed
+}
+
+impl SkipOnIncident {
+
+    /// the value of the skip_on_incident parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipOnIncident::AllIncidents => "all_incidents",
+            SkipOnIncident::ParsingIncidents => "parsing_incidents",
+        }
+    }
+}
+
+impl Params {
+
+    /// create a new Params object for a web search
+    /// An empty location means the search is not geotargeted, see without_location
+    pub fn new(api_key: &str, q: &str, location: &str) -> Self {
+        Params {
+            api_key: api_key.to_string(),
+            location: if location.trim().is_empty() { None } else { Some(location.to_string()) },
+            location_auto: None,
+            q: q.to_string(),
+            async_mode: false,
+            search_type: None,
+            skip_on_incident: None,
+            num: None,
+            device: None,
+            hl: None,
+            gl: None,
+            google_domain: None,
+            cr: None,
+            as_sitesearch: None,
+            safe: None,
+            filter: None,
+            tag: None,
+        }
+    }
+
+    /// create a new Params object for q with every other setting read from the environment
+    /// SCALE_SERP_KEY and SCALE_SERP_LOCATION are required. SCALE_SERP_DEVICE ("desktop", "mobile" or "tablet"),
+    /// SCALE_SERP_HL, SCALE_SERP_GL, SCALE_SERP_SAFE ("on" or "off") and SCALE_SERP_NUM are optional
+    pub fn from_env(q: &str) -> Result<Self, Error> {
+        Params::from_vars(q, |name| env::var(name).ok())
+    }
+
+    /// from_env, reading variables with var
+    fn from_vars(q: &str, var: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        let var = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+        let missing: Vec<String> = ["SCALE_SERP_KEY", "SCALE_SERP_LOCATION"].iter()
+            .filter(|name| var(name).is_none())
+            .map(|name| name.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingEnvVars(missing));
+        }
+        let invalid = |name: &str, value: String| Error::InvalidEnvVar { name: name.to_string(), value };
+        let mut params = Params::new(&var("SCALE_SERP_KEY").unwrap_or_default(), q, &var("SCALE_SERP_LOCATION").unwrap_or_default());
+        if let Some(device) = var("SCALE_SERP_DEVICE") {
+            params.device = Some(Device::from_name(&device).ok_or_else(|| invalid("SCALE_SERP_DEVICE", device))?);
+        }
+        params.hl = var("SCALE_SERP_HL");
+        params.gl = var("SCALE_SERP_GL");
+        if let Some(safe) = var("SCALE_SERP_SAFE") {
+            params.safe = Some(match safe.trim().to_lowercase().as_str() {
+                "on" | "active" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                _ => return Err(invalid("SCALE_SERP_SAFE", safe)),
+            });
+        }
+        if let Some(num) = var("SCALE_SERP_NUM") {
+            params.num = Some(num.trim().parse().map_err(|_| invalid("SCALE_SERP_NUM", num))?);
+        }
+        Ok(params)
+    }
+
+    pub fn new_env(q: &str, location: &str) -> Self {
+        // create a new Params object using an api_key from an environment variable
+        let api_key = match env::var("SCALE_SERP_KEY") {
+            Ok(val) => val,
+            Err(_) => "".to_string(),
+        };
+        Params::new(&api_key, q, location)
+    }
+
+    /// create a new Params object for a search that is not geotargeted, using SCALE_SERP_KEY
+    pub fn new_env_without_location(q: &str) -> Self {
+        Params::new_env(q, "")
+    }
+
+    /// create a new Params object for a search from New York City
+    /// Note searches are more likely to include ads if they are targeted to a specific geographic location
+    pub fn new_env_nyc(q: &str) -> Self {
+        Params::new_env(q, "New York,New York,United States")
+    }
+
+    /// create a new Params object for a search within the United States
+    pub fn new_env_usa(q: &str) -> Self {
+        Params::new_env(q, "United States")
+    }
+
+
+    /// use the asynchronous API: search with ScaleSerpClient::search_async to get an AsyncJobResp
+    pub fn with_async(mut self, async_mode: bool) -> Self {
+        self.async_mode = async_mode;
+        self
+    }
+
+    /// run a news, images, shopping, videos or places search instead of a web search
+    pub fn with_search_type(mut self, search_type: SearchType) -> Self {
+        self.search_type = Some(search_type);
+        self
+    }
+
+    /// skip the search, without charging for it, while Google has an incident
+    pub fn with_skip_on_incident(mut self, skip_on_incident: SkipOnIncident) -> Self {
+        self.skip_on_incident = Some(skip_on_incident);
+        self
+    }
+
+    /// ask for num results per page instead of 10
+    pub fn with_num(mut self, num: usize) -> Self {
+        self.num = Some(num);
+        self
+    }
+
+    /// search for the query built by query, i.e. `params.with_query(&site_search("example.com", "pricing"))?`
+    pub fn with_query(mut self, query: &QueryBuilder) -> Result<Self, Error> {
+        self.q = query.build()?;
+        Ok(self)
+    }
+
+    /// search from location, i.e. "Boston,Massachusetts,United States"
+    pub fn with_location(mut self, location: &str) -> Self {
+        self.location = Some(location.to_string());
+        self
+    }
+
+    /// do not geotarget the search: the location parameter is left out
+    pub fn without_location(mut self) -> Self {
+        self.location = None;
+        self
+    }
+
+    /// let ScaleSERP infer a location from the query (true), or stop it from doing so (false)
+    pub fn with_location_auto(mut self, location_auto: bool) -> Self {
+        self.location_auto = Some(location_auto);
+        self
+    }
+
+    /// let ScaleSERP pick the location itself (location_auto=true) instead of sending one
+    /// This clears any location set before, as the two can not be combined
+    pub fn with_auto_location(mut self) -> Self {
+        self.location = None;
+        self.location_auto = Some(true);
+        self
+    }
+
+    /// search as if from a mobile, tablet or desktop device
+    pub fn with_device(mut self, device: Device) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// set the interface language, i.e. "en"
+    pub fn with_hl(mut self, hl: &str) -> Self {
+        self.hl = Some(hl.to_string());
+        self
+    }
+
+    /// set the country to search from, i.e. "us"
+    pub fn with_gl(mut self, gl: &str) -> Self {
+        self.gl = Some(gl.to_string());
+        self
+    }
+
+    /// search on this Google domain, i.e. "google.co.uk"
+    pub fn with_google_domain(mut self, google_domain: &str) -> Self {
+        self.google_domain = Some(google_domain.to_string());
+        self
+    }
+
+    /// only return pages from these countries, i.e. "countryUS", or CountryRestrict::UnitedStates.as_str()
+    /// Join several countries with "|", i.e. "countryUS|countryCA"
+    pub fn with_cr(mut self, country_restrict: &str) -> Self {
+        self.cr = Some(country_restrict.to_string());
+        self
+    }
+
+    /// only return pages from site, i.e. "example.com", without adding "site:" to the query
+    /// QueryBuilder::site is the same restriction written into the query instead
+    pub fn with_as_sitesearch(mut self, site: &str) -> Self {
+        self.as_sitesearch = Some(site.to_string());
+        self
+    }
+
+    /// turn SafeSearch on or off
+    pub fn with_safe(mut self, safe: bool) -> Self {
+        self.safe = Some(safe);
+        self
+    }
+
+    /// turn Google's filtering of very similar results on or off
+    pub fn with_filter(mut self, filter: DuplicateFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// label the search for credit attribution, see ScaleSerpClient::credits_by_tag
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn to_url(&self) -> String {
+        // give the URL associated with these parameters
+        query::url_with_pairs(self.endpoint(), &self.effective_pairs())
+    }
+
+    /// the endpoint the search is sent to, which depends on async_mode
+    pub fn endpoint(&self) -> &'static str {
+        if self.async_mode { ASYNC_SEARCH_URL } else { SEARCH_URL }
+    }
+
+    /// every parameter that is sent, in URL order and before encoding, including the API key
+    /// ScaleSerpClient::effective_pairs adds what the client sends on top of these
+    pub fn effective_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![("api_key", self.api_key.clone())];
+        if let Some(location) = &self.location {
+            pairs.push(("location", location.clone()));
+        }
+        pairs.push(("q", self.q.clone()));
+        if let Some(location_auto) = self.location_auto {
+            pairs.push(("location_auto", location_auto.to_string()));
+        }
+        if let Some(search_type) = self.search_type {
+            pairs.push(("search_type", search_type.as_str().to_string()));
+        }
+        if let Some(num) = self.num {
+            pairs.push(("num", num.to_string()));
+        }
+        if let Some(device) = self.device {
+            pairs.push(("device", device.as_str().to_string()));
+        }
+        if let Some(hl) = &self.hl {
+            pairs.push(("hl", hl.clone()));
+        }
+        if let Some(gl) = &self.gl {
+            pairs.push(("gl", gl.clone()));
+        }
+        if let Some(google_domain) = &self.google_domain {
+            pairs.push(("google_domain", google_domain.clone()));
+        }
+        if let Some(cr) = &self.cr {
+            pairs.push(("cr", cr.clone()));
+        }
+        if let Some(as_sitesearch) = &self.as_sitesearch {
+            pairs.push(("as_sitesearch", as_sitesearch.clone()));
+        }
+        if let Some(safe) = self.safe {
+            pairs.push(("safe", if safe { "active" } else { "off" }.to_string()));
+        }
+        if let Some(filter) = self.filter {
+            pairs.push(("filter", filter.as_str().to_string()));
+        }
+        if let Some(skip_on_incident) = self.skip_on_incident {
+            pairs.push(("skip_on_incident", skip_on_incident.as_str().to_string()));
+        }
+        pairs
+    }
+
+    /// the URL with the API key replaced by "***", safe to log
+    pub fn redacted_url(&self) -> String {
+        query::redact_api_key(&self.to_url())
+    }
+
+    /// shorten the query to at most max_chars characters, cutting at a word boundary where possible
+    /// Use this for long scraped questions that would otherwise make the URL too long
+    pub fn truncate_query(&mut self, max_chars: usize) {
+        if self.q.chars().count() <= max_chars {
+            return;
+        }
+        let cut = self.q.char_indices().nth(max_chars).map_or(self.q.len(), |(i, _)| i);
+        let truncated = &self.q[..cut];
+        // only cut at whitespace if the next character does not start a new word anyway
+        let at_boundary = self.q[cut..].starts_with(char::is_whitespace);
+        let truncated = match truncated.rfind(char::is_whitespace) {
+            Some(space) if !at_boundary && space > 0 => &truncated[..space],
+            _ => truncated,
+        };
+        self.q = truncated.trim_end().to_string();
+    }
+
+    /// check the params make a search worth sending, without sending it
+    /// A query that is empty or only whitespace is Error::InvalidQuery: ScaleSERP would charge a credit for an empty response.
+    /// A location together with location_auto=true is Error::InvalidConfig
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.q.trim().is_empty() {
+            return Err(Error::InvalidQuery("the query is empty".to_string()));
+        }
+        if self.location.is_some() && self.location_auto == Some(true) {
+            return Err(Error::InvalidConfig("location and location_auto=true can not be combined".to_string()));
+        }
+        Ok(())
+    }
+
+
+}
+
+
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[serde(from = "RawResp")]
+pub struct Resp {
+    // This is the top-level object representing a response from ScaleSERP
+    // Blocks that are missing from the payload are deserialized as empty: see had_ads_block etc. to tell the two apart
+    pub request_info: RequestInfo,
+    pub search_metadata: SearchMetadata,
+    pub search_parameters: SearchParameters,
+    pub search_information: SearchInformation,
+    pub ads: Vec<Ad>,
+    //pub inline_images: String,
+    pub top_stories: Vec<TopStory>, // will be empty for 'uncommon' search terms
+    //pub top_stories_extra: String,
+    pub top_products: Vec<TopProduct>,
+    //pub local_map: MOSTLY JUST B64 IMAGES,
+    //pub local_results: MOSTLY JUST B64 IMAGES,,
+    pub related_searches: Vec<RelatedSearch>,
+    pub related_questions: Vec<RelatedQuestion>,
+    //pub pagination: String,
+    pub organic_results: Vec<OrganicResult>,
+    pub app_results: Option<Vec<AppResult>>, // Google Play or App Store results
+    pub dictionary_result: Option<DictionaryResult>, // for definition queries
+    pub weather_result: Option<WeatherResult>, // for weather queries
+    pub conversion_result: Option<ConversionResult>, // for unit and currency conversions
+    pub calculator_result: Option<CalculatorResult>, // for math expressions
+    pub hotel_results: Option<Vec<HotelResult>>, // for hotel searches
+    pub sports_results: Option<SportsResult>, // for scores and standings
+    pub finance_result: Option<FinanceResult>, // for stock and market queries
+    #[serde(skip)]
+    blocks_present: BlocksPresent,
+}
+
+impl From<&Resp> for serde_json::Value {
+    /// the response as untyped JSON, in the shape Resp serializes to
+    fn from(resp: &Resp) -> Self {
+        serde_json::to_value(resp).expect("a response always serializes")
+    }
+}
+
+/// The layout signatures of two responses that differ, see Resp::layout_signature
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LayoutChange {
+    pub before: String,
+    pub after: String,
+}
+
+/// Which optional blocks were in the payload, even if they were empty
+#[derive(Debug, Default, Clone, Copy)]
+struct BlocksPresent {
+    ads: bool,
+    top_stories: bool,
+    top_products: bool,
+    related_questions: bool,
+}
+
+/// Resp exactly as it appears in the payload, before missing blocks are replaced with empty ones
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct RawResp {
+    request_info: RequestInfo,
+    search_metadata: SearchMetadata,
+    search_parameters: SearchParameters,
+    search_information: SearchInformation,
+    ads: Option<Vec<Ad>>,
+    #[serde(default, deserialize_with = "parse::top_stories")]
+    top_stories: Option<Vec<TopStory>>,
+    top_products: Option<Vec<TopProduct>>,
+    #[serde(default)]
+    related_searches: Vec<RelatedSearch>,
+    #[serde(default, deserialize_with = "parse::related_questions")]
+    related_questions: Option<Vec<RelatedQuestion>>,
+    #[serde(default, deserialize_with = "parse::organic_results")]
+    organic_results: Vec<OrganicResult>, // missing from news, images and other vertical searches
+    app_results: Option<Vec<AppResult>>,
+    dictionary_result: Option<DictionaryResult>,
+    weather_result: Option<WeatherResult>,
+    conversion_result: Option<ConversionResult>,
+    calculator_result: Option<CalculatorResult>,
+    hotel_results: Option<Vec<HotelResult>>,
+    sports_results: Option<SportsResult>,
+    finance_result: Option<FinanceResult>,
+}
+
+/// Resp as it is serialized: optional blocks that were missing from the payload are left out again,
+/// so had_ads_block etc. give the same answers after saving and loading a response
+#[derive(Serialize)]
+struct SerializedResp<'a> {
+    request_info: &'a RequestInfo,
+    search_metadata: &'a SearchMetadata,
+    search_parameters: &'a SearchParameters,
+    search_information: &'a SearchInformation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ads: Option<&'a Vec<Ad>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_stories: Option<&'a Vec<TopStory>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_products: Option<&'a Vec<TopProduct>>,
+    related_searches: &'a Vec<RelatedSearch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    related_questions: Option<&'a Vec<RelatedQuestion>>,
+    organic_results: &'a Vec<OrganicResult>,
+    app_results: &'a Option<Vec<AppResult>>,
+    dictionary_result: &'a Option<DictionaryResult>,
+    weather_result: &'a Option<WeatherResult>,
+    conversion_result: &'a Option<ConversionResult>,
+    calculator_result: &'a Option<CalculatorResult>,
+    hotel_results: &'a Option<Vec<HotelResult>>,
+    sports_results: &'a Option<SportsResult>,
+    finance_result: &'a Option<FinanceResult>,
+}
+
+impl Serialize for Resp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // a block is written if it was in the payload, or has had results added since
+        fn block<T>(present: bool, results: &Vec<T>) -> Option<&Vec<T>> {
+            (present || !results.is_empty()).then_some(results)
+        }
+        let present = &self.blocks_present;
+        SerializedResp {
+            request_info: &self.request_info,
+            search_metadata: &self.search_metadata,
+            search_parameters: &self.search_parameters,
+            search_information: &self.search_information,
+            ads: block(present.ads, &self.ads),
+            top_stories: block(present.top_stories, &self.top_stories),
+            top_products: block(present.top_products, &self.top_products),
+            related_searches: &self.related_searches,
+            related_questions: block(present.related_questions, &self.related_questions),
+            organic_results: &self.organic_results,
+            app_results: &self.app_results,
+            dictionary_result: &self.dictionary_result,
+            weather_result: &self.weather_result,
+            conversion_result: &self.conversion_result,
+            calculator_result: &self.calculator_result,
+            hotel_results: &self.hotel_results,
+            sports_results: &self.sports_results,
+            finance_result: &self.finance_result,
+        }.serialize(serializer)
+    }
+}
+
+impl From<RawResp> for Resp {
+    fn from(raw: RawResp) -> Self {
+        let blocks_present = BlocksPresent {
+            ads: raw.ads.is_some(),
+            top_stories: raw.top_stories.is_some(),
+            top_products: raw.top_products.is_some(),
+            related_questions: raw.related_questions.is_some(),
+        };
+        Resp {
+            request_info: raw.request_info,
+            search_metadata: raw.search_metadata,
+            search_parameters: raw.search_parameters,
+            search_information: raw.search_information,
+            ads: raw.ads.unwrap_or_default(),
+            top_stories: raw.top_stories.unwrap_or_default(),
+            top_products: raw.top_products.unwrap_or_default(),
+            related_searches: raw.related_searches,
+            related_questions: raw.related_questions.unwrap_or_default(),
+            organic_results: raw.organic_results,
+            app_results: raw.app_results,
+            dictionary_result: raw.dictionary_result,
+            weather_result: raw.weather_result,
+            conversion_result: raw.conversion_result,
+            calculator_result: raw.calculator_result,
+            hotel_results: raw.hotel_results,
+            sports_results: raw.sports_results,
+            finance_result: raw.finance_result,
+            blocks_present,
+        }
+    }
+}
+
+impl Resp {
+
+    /// true if the payload had an ads block, even an empty one
+    pub fn had_ads_block(&self) -> bool {
+        self.blocks_present.ads
+    }
+
+    /// true if the payload had a top_stories block, even an empty one
+    pub fn had_top_stories_block(&self) -> bool {
+        self.blocks_present.top_stories
+    }
+
+    /// true if the payload had a top_products block, even an empty one
+    pub fn had_top_products_block(&self) -> bool {
+        self.blocks_present.top_products
+    }
+
+    /// true if the payload had a related_questions block, even an empty one
+    pub fn had_related_questions_block(&self) -> bool {
+        self.blocks_present.related_questions
+    }
+
+    /// the organic result at position, which is not necessarily its index in organic_results
+    pub fn organic_result_at(&self, position: usize) -> Option<&OrganicResult> {
+        self.organic_results.iter().find(|result| result.position == position)
+    }
+
+    /// true if ScaleSERP could not use the requested location and picked one automatically instead
+    /// Always false for searches that did not request a location, see location_status
+    pub fn location_fell_back(&self) -> bool {
+        self.location_status() == LocationStatus::FellBack
+    }
+
+    /// whether the search requested a location, and if so whether ScaleSERP used it
+    pub fn location_status(&self) -> LocationStatus {
+        if self.search_parameters.location.trim().is_empty() {
+            LocationStatus::NotRequested
+        } else if self.search_metadata.location_auto_message.is_some() {
+            LocationStatus::FellBack
+        } else {
+            LocationStatus::Used
+        }
+    }
+
+    /// how the query Google displayed differs from the query that was sent, None if they match
+    pub fn query_discrepancy(&self) -> Option<QueryDiscrepancy> {
+        QueryDiscrepancy::classify(&self.search_parameters.q, &self.search_information.query_displayed)
+    }
+
+    /// links to Google's cached copies of the organic results, for those that have one
+    pub fn cached_links(&self) -> Vec<&str> {
+        self.organic_results.iter()
+            .filter_map(|result| result.cached_page_link.as_deref())
+            .collect()
+    }
+
+    /// the organic results sorted alphabetically by domain, keeping their original order within a domain
+    /// Domains are compared ignoring case and "www.", so www.example.com and example.com sort together
+    pub fn organic_results_sorted_by_domain(&self) -> Vec<&OrganicResult> {
+        let mut results: Vec<&OrganicResult> = self.organic_results.iter().collect();
+        results.sort_by_cached_key(|result| normalize_domain(&result.domain));
+        results
+    }
+
+    /// whether each of questions is among the related questions ("People also ask"), i.e. to find FAQ gaps
+    /// Questions are compared with normalize_keyword, ignoring a trailing question mark
+    pub fn question_coverage<'a>(&self, questions: &[&'a str]) -> HashMap<&'a str, bool> {
+        let fold = |question: &str| normalize_keyword(question.trim_end().trim_end_matches('?'));
+        let asked: Vec<String> = self.related_questions.iter().map(|question| fold(&question.question)).collect();
+        questions.iter().map(|question| (*question, asked.contains(&fold(question)))).collect()
+    }
+
+    /// the top stories grouped by the name of their news source, each group in SERP order
+    pub fn top_stories_by_source(&self) -> HashMap<&str, Vec<&TopStory>> {
+        let mut grouped: HashMap<&str, Vec<&TopStory>> = HashMap::new();
+        for story in &self.top_stories {
+            grouped.entry(story.source.as_str()).or_default().push(story);
+        }
+        grouped
+    }
+
+    /// the news sources of the top stories without duplicates, in the order they first appear
+    pub fn unique_news_sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = Vec::new();
+        for story in &self.top_stories {
+            if !sources.contains(&story.source.as_str()) {
+                sources.push(&story.source);
+            }
+        }
+        sources
+    }
+
+    /// signs the response is not a normal results page for the query, empty for a normal response
+    /// A difference in case alone is not a rewrite, and neither is a page with no query displayed at all
+    pub fn warnings(&self) -> Vec<SerpWarning> {
+        let mut warnings = Vec::new();
+        if self.is_probable_consent_wall() {
+            warnings.push(SerpWarning::ProbableConsentWall);
+        } else if !self.search_information.query_displayed.trim().is_empty() {
+            match self.query_discrepancy() {
+                None | Some(QueryDiscrepancy::CaseOnly) => {},
+                Some(discrepancy) => warnings.push(SerpWarning::QueryRewritten(discrepancy)),
+            }
+        }
+        warnings
+    }
+
+    /// true if the response looks like Google's cookie consent page: no results or features of any kind,
+    /// and no query displayed. A query that really has no results still has its query displayed,
+    /// and usually original_query_yields_zero_results set
+    fn is_probable_consent_wall(&self) -> bool {
+        let information = &self.search_information;
+        self.organic_results.is_empty()
+            && SerpFeatures::of(self) == SerpFeatures::default()
+            && information.total_results == 0
+            && !information.original_query_yields_zero_results
+            && information.query_displayed.trim().is_empty()
+    }
+
+    /// the organic results Google shows a date for, i.e. to separate time-sensitive pages from timeless ones
+    pub fn organic_results_with_date(&self) -> Vec<&OrganicResult> {
+        self.organic_results.iter().filter(|result| result.date.is_some()).collect()
+    }
+
+    /// the top-level keys of a raw payload that Resp does not model, and are dropped when deserializing
+    /// i.e. ["inline_images", "pagination"]. Empty if raw is not a JSON object
+    pub fn unmodeled_blocks(raw: &serde_json::Value) -> Vec<String> {
+        match raw.as_object() {
+            Some(blocks) => blocks.keys()
+                .filter(|key| !schema::is_supported_block(key))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// how many pages of results you could request with params, to plan a pagination loop
+    /// Google stops serving results after about MAX_PAGINATED_RESULTS, so this is capped there
+    pub fn estimated_total_pages(&self, params: &Params) -> usize {
+        let per_page = params.num.unwrap_or(DEFAULT_RESULTS_PER_PAGE).max(1);
+        let results = self.search_information.total_results.min(MAX_PAGINATED_RESULTS);
+        results.div_ceil(per_page)
+    }
+
+    /// true if Google left out very similar results, which a search with DuplicateFilter::Disabled would show
+    pub fn has_omitted_results(&self) -> bool {
+        self.search_information.omitted_results.is_some()
+    }
+
+    /// the snippet of every organic result, in order
+    pub fn organic_snippets(&self) -> Vec<&str> {
+        self.organic_results.iter().map(|result| result.snippet.as_str()).collect()
+    }
+
+    /// a search for each "People also ask" question, with every other setting copied from base
+    /// Run them with ScaleSerpClient::search_many to dig deeper into a topic
+    pub fn follow_up_searches(&self, base: &Params) -> Vec<Params> {
+        self.related_questions.iter().map(|question| question.to_params(base)).collect()
+    }
+
+    /// how often each lowercase word appears across the organic snippets, leaving out stop words
+    /// Words are split at anything other than letters, digits and apostrophes. None uses WordCloudOptions::default
+    pub fn word_cloud_data(&self, word_cloud_options: Option<&WordCloudOptions>) -> HashMap<String, usize> {
+        let default_options = WordCloudOptions::default();
+        let options = word_cloud_options.unwrap_or(&default_options);
+        let mut counts = HashMap::new();
+        for snippet in self.organic_snippets() {
+            for word in snippet.split(|c: char| !c.is_alphanumeric() && c != '\'') {
+                let word = word.trim_matches('\'').to_lowercase();
+                if word.is_empty() || options.stop_words.contains(&word) {
+                    continue;
+                }
+                *counts.entry(word).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// the position of the organic result whose link is exactly url
+    pub fn organic_rank(&self, url: &str) -> Option<usize> {
+        self.organic_results.iter()
+            .find(|result| result.link.as_str() == url)
+            .map(|result| result.position)
+    }
+
+    /// how many organic results are on domain or one of its subdomains, ignoring case and "www.", see domain_matches
+    pub fn organic_result_count_for_domain(&self, domain: &str) -> usize {
+        self.organic_results.iter().filter(|result| domain_matches(&result.domain, domain)).count()
+    }
+
+    /// true if domain (or one of its subdomains) appears in the organic results, ads or top product sources
+    pub fn contains_domain(&self, domain: &str) -> bool {
+        self.organic_results.iter().any(|result| domain_matches(&result.domain, domain))
+            || self.ads.iter().any(|ad| domain_matches(&ad.domain, domain))
+            || self.top_products.iter()
+                .flat_map(|product| product.sources.iter())
+                .any(|source| source.link.is_on_domain(domain))
+    }
+
+    /// organic results whose snippet is detected as a language other than expected (i.e. "en")
+    /// Results whose language cannot be detected are not included
+    pub fn language_mismatches(&self, expected: &str, detector: &impl LanguageDetector) -> Vec<&OrganicResult> {
+        self.organic_results.iter()
+            .filter(|result| match result.detected_language(detector) {
+                Some(lang) => lang != expected,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// top products with a parsed price between min and max (inclusive)
+    pub fn products_in_price_range(&self, min: f64, max: f64) -> Vec<&TopProduct> {
+        self.top_products.iter()
+            .filter(|product| match product.price_value() {
+                Some(price) => min <= price && price <= max,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// the ads shown above the organic results
+    pub fn ads_above_fold(&self) -> Vec<&Ad> {
+        self.ads.iter().filter(|ad| ad.block_position.eq_ignore_ascii_case("top")).collect()
+    }
+
+    /// the ads shown below the organic results
+    pub fn ads_below_fold(&self) -> Vec<&Ad> {
+        self.ads.iter().filter(|ad| ad.block_position.eq_ignore_ascii_case("bottom")).collect()
+    }
+
+    /// the order of the blocks on the page as a compact code, i.e. "A2 O1 P2 S3 Q2 O3 A1"
+    /// Each run of results of one kind is its letter and count: A ads, O organic results, S top stories, P top products
+    /// and Q related questions. Top ads come first and bottom ads last, everything else is ordered by block_position
+    pub fn layout_signature(&self) -> String {
+        let mut items: Vec<((usize, usize), char)> = Vec::new();
+        for ad in &self.ads {
+            let group = if ad.block_position.eq_ignore_ascii_case("bottom") { 2 } else { 0 };
+            items.push(((group, 0), 'A'));
+        }
+        items.extend(self.organic_results.iter().map(|result| ((1, result.block_position), 'O')));
+        items.extend(self.top_stories.iter().map(|story| ((1, story.block_position), 'S')));
+        items.extend(self.top_products.iter().map(|product| ((1, product.block_position), 'P')));
+        items.extend(self.related_questions.iter().map(|question| ((1, question.block_position), 'Q')));
+        items.sort_by_key(|(key, _)| *key); // stable, so results keep their order within a block
+        let mut runs: Vec<(char, usize)> = Vec::new();
+        for (_, kind) in items {
+            match runs.last_mut() {
+                Some((last, count)) if *last == kind => *count += 1,
+                _ => runs.push((kind, 1)),
+            }
+        }
+        runs.iter().map(|(kind, count)| format!("{}{}", kind, count)).collect::<Vec<String>>().join(" ")
+    }
+
+    /// how the layout changed since earlier, None if it is the same
+    /// Compare responses for the same query and location, or the change says more about the query than about Google
+    pub fn layout_change(&self, earlier: &Resp) -> Option<LayoutChange> {
+        let before = earlier.layout_signature();
+        let after = self.layout_signature();
+        if before == after {
+            return None;
+        }
+        Some(LayoutChange { before, after })
+    }
+
+    /// the keywords the ads were matched on, without duplicates, in the order they first appear
+    /// Ads without matched parts contribute the words of their title instead
+    pub fn ad_keywords(&self) -> Vec<&str> {
+        let mut keywords: Vec<&str> = Vec::new();
+        for ad in &self.ads {
+            let ad_keywords: Vec<&str> = match &ad.matched {
+                Some(matched) if !matched.is_empty() => matched.iter().map(|m| m.trim()).collect(),
+                _ => ad.title.split_whitespace()
+                    .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+                    .collect(),
+            };
+            for keyword in ad_keywords {
+                if !keyword.is_empty() && !keywords.contains(&keyword) {
+                    keywords.push(keyword);
+                }
+            }
+        }
+        keywords
+    }
+
+    /// group the top products by the merchants (source names) selling them
+    pub fn products_by_merchant(&self) -> HashMap<&str, Vec<&TopProduct>> {
+        let mut by_merchant: HashMap<&str, Vec<&TopProduct>> = HashMap::new();
+        for product in &self.top_products {
+            for source in &product.sources {
+                by_merchant.entry(source.name.as_str()).or_default().push(product);
+            }
+        }
+        by_merchant
+    }
+
+    /// the top product with the highest rating
+    pub fn highest_rated_product(&self) -> Option<&TopProduct> {
+        self.top_products.iter()
+            .max_by(|a, b| a.rating.total_cmp(&b.rating))
+    }
+
+    /// the top product with the most reviews, ignoring products without a review count
+    pub fn most_reviewed_product(&self) -> Option<&TopProduct> {
+        self.top_products.iter()
+            .filter(|product| product.reviews_count.is_some())
+            .max_by_key(|product| product.reviews_count)
+    }
+}
+
+/// What came back from a search that may have been skipped because of a Google incident
+#[derive(Debug)]
+pub enum SearchOutcome {
+    Completed(Box<Resp>),
+    SkippedIncident(IncidentSkip), // nothing was charged: requeue the search for later
+}
+
+/// A search ScaleSERP skipped because of skip_on_incident
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncidentSkip {
+    pub message: String, // the explanation ScaleSERP gave
+}
+
+impl SearchOutcome {
+
+    /// the response, if the search was not skipped
+    pub fn completed(self) -> Option<Resp> {
+        match self {
+            SearchOutcome::Completed(resp) => Some(*resp),
+            SearchOutcome::SkippedIncident(_) => None,
+        }
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, SearchOutcome::SkippedIncident(_))
+    }
+
+    /// the outcome a response already read as JSON describes, without copying it
+    pub(crate) fn from_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+        if let Some(skip) = incident_skip(value) {
+            return Ok(SearchOutcome::SkippedIncident(skip));
+        }
+        Ok(SearchOutcome::Completed(Box::new(Resp::deserialize(value)?)))
+    }
+}
+
+impl<'de> Deserialize<'de> for SearchOutcome {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        SearchOutcome::from_value(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// the skip, if value is the unsuccessful response ScaleSERP sends when skip_on_incident applied
+fn incident_skip(value: &serde_json::Value) -> Option<IncidentSkip> {
+    let request_info = value.get("request_info")?;
+    if request_info.get("success")?.as_bool()? {
+        return None;
+    }
+    let message = request_info.get("message")?.as_str()?;
+    if !message.to_lowercase().contains("incident") {
+        return None;
+    }
+    Some(IncidentSkip { message: message.to_string() })
+}
+
+/// The response to a search sent with the asynchronous API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct AsyncJobResp {
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RequestInfo {
+    pub success: bool,
+    pub credits_used: usize,
+    pub credits_used_this_request: usize,
+    pub credits_remaining: usize,
+    pub credits_reset_at: String,  // i.e. '2021-07-31T01:00:37.000Z'
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchParameters {
+    // this is basically your query parameters returned in the response
+    #[serde(default)]
+    pub location: String, // empty if the search was not geotargeted
+    pub q: String, //  your query string
+    #[serde(default)]
+    pub device: Option<String>, // i.e. "mobile", missing for desktop searches
+    #[serde(default)]
+    pub google_domain: Option<String>, // i.e. "google.co.uk"
+    #[serde(default)]
+    pub gl: Option<String>,
+    #[serde(default)]
+    pub hl: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_page")]
+    pub page: Option<usize>, // missing for the first page
+}
+
+/// deserialize a page number, which ScaleSERP echoes back as a string, i.e. "2"
+fn deserialize_page<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::Number(page)) => Ok(page.as_u64().map(|page| page as usize)),
+        Some(serde_json::Value::String(page)) => Ok(page.trim().parse().ok()),
+        _ => Ok(None),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchMetadata {
+    pub created_at: String,
+    pub processed_at: String,
+    pub total_time_taken: f64,
+    pub engine_url: String,
+    pub html_url: String,
+    pub json_url: String,
+    pub location_auto_message: Option<String>,
+}
+
+impl SearchMetadata {
+
+    /// the Google URL ScaleSERP searched, i.e. to read its q, gl or hl parameters
+    pub fn engine_url_parsed(&self) -> Result<url::Url, url::ParseError> {
+        url::Url::parse(&self.engine_url)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchInformation {
+    pub original_query_yields_zero_results: bool,
+    pub total_results: usize,
+    pub time_taken_displayed: f64,
+    pub query_displayed: String,
+    pub detected_location: Option<String>,
+    #[serde(default)]
+    pub omitted_results: Option<String>, // Google's notice that very similar results were left out, see DuplicateFilter
+}
+
+
+
+/// the stop words word_cloud_data leaves out by default
+pub const DEFAULT_STOP_WORDS: [&str; 40] = [
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "can", "for", "from", "has", "have", "how", "if", "in", "is", "it", "its",
+    "more", "not", "of", "on", "or", "our", "so", "that", "the", "their", "this", "to", "was", "we", "what", "when", "which", "with", "you", "your",
+];
+
+/// Settings for Resp::word_cloud_data
+#[derive(Debug, Clone)]
+pub struct WordCloudOptions {
+    pub stop_words: Vec<String>, // lowercase words to leave out, DEFAULT_STOP_WORDS by default
+}
+
+impl Default for WordCloudOptions {
+    fn default() -> Self {
+        WordCloudOptions { stop_words: DEFAULT_STOP_WORDS.iter().map(|word| word.to_string()).collect() }
+    }
+}
+
+/// What happened to the location a search asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationStatus {
+    NotRequested, // the search was not geotargeted
+    Used,
+    FellBack, // ScaleSERP could not use the location and picked one automatically
+}
+
+/// Something about a response that suggests it is not the results page for the query that was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerpWarning {
+    ProbableConsentWall, // Google showed its cookie consent page instead of results, typically for EU domains
+    QueryRewritten(QueryDiscrepancy), // Google searched for a different query than the one sent. Never CaseOnly
+}
+
+/// How the query Google displayed differs from the query that was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryDiscrepancy {
+    CaseOnly, // i.e. "Taylor Swift" vs "taylor swift"
+    PunctuationOnly, // i.e. "what's up?" vs "whats up"
+    SpellingFix, // the same number of words, each at most two edits apart
+    OperatorRemoved, // operators like site: or -word were stripped
+    Other,
+}
+
+impl QueryDiscrepancy {
+
+    /// classify the difference between the query sent and the query displayed, None if they are identical
+    pub fn classify(sent: &str, displayed: &str) -> Option<Self> {
+        if sent == displayed {
+            return None;
+        }
+        if sent.to_lowercase() == displayed.to_lowercase() {
+            return Some(QueryDiscrepancy::CaseOnly);
+        }
+        let sent_words = words(sent);
+        let displayed_words = words(displayed);
+        if sent_words == displayed_words {
+            return Some(QueryDiscrepancy::PunctuationOnly);
+        }
+        let without_operators: Vec<String> = sent.split_whitespace()
+            .filter(|token| !is_operator(token))
+            .flat_map(words)
+            .collect();
+        if without_operators.len() < sent_words.len() && without_operators == displayed_words {
+            return Some(QueryDiscrepancy::OperatorRemoved);
+        }
+        if sent_words.len() == displayed_words.len()
+            && sent_words.iter().zip(displayed_words.iter()).all(|(a, b)| edit_distance(a, b) <= 2) {
+            return Some(QueryDiscrepancy::SpellingFix);
+        }
+        Some(QueryDiscrepancy::Other)
+    }
+}
+
+/// the lowercase words of a query without any punctuation
+fn words(query: &str) -> Vec<String> {
+    query.split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// true for search operators like site:example.com, -word or OR
+fn is_operator(token: &str) -> bool {
+    const PREFIXES: [&str; 7] = ["site:", "intitle:", "inurl:", "intext:", "filetype:", "related:", "cache:"];
+    token == "OR" || token == "AND"
+        || (token.starts_with('-') && token.len() > 1)
+        || PREFIXES.iter().any(|prefix| token.to_lowercase().starts_with(prefix))
+}
+
+/// the Levenshtein distance between two words
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct Ad {
+    pub position: usize, 
+    pub block_position: String,
+    pub title: String,
+    pub link: ResultLink,
+    pub domain: String,
+    pub displayed_link: String,
+    pub description: String,
+    pub sitelinks: Option<Vec<AdSitelink>>,
+    #[serde(default, alias = "highlights", alias = "description_matched")]
+    pub matched: Option<Vec<String>>, // the parts of the ad Google bolded because they matched the query
+}
+
+impl Ad {
+
+    /// the link of the ad parsed as a URL, see ResultLink::parsed
+    pub fn link_parsed(&self) -> Result<url::Url, url::ParseError> {
+        self.link.parsed()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct AdSitelink {
+    pub title: String,
+    pub link: ResultLink,
+    pub description: Option<String>, // only on expanded sitelinks
+    #[serde(default)]
+    pub links: Option<Vec<AdSitelink>>, // deep links nested under an expanded sitelink
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct OrganicResult {
+    pub position: usize,
+    pub title: String,
+    pub link: ResultLink,
+    pub domain: String,
+    pub displayed_link: String,
+    pub snippet: String, 
+    pub prerender: bool,
+    pub snippet_matched: Option<Vec<String>>, 
+    pub block_position: usize, 
+    pub cached_page_link: Option<String>, // Google's cached copy of the page
+    pub date: Option<String>, // the date Google shows before the snippet, i.e. "Jul 6, 2022". Missing for undated pages
+    #[serde(default, deserialize_with = "deserialize_image_url")]
+    pub thumbnail: Option<String>, // inline base64 images are dropped, only real URLs are kept
+    pub rich_snippet: Option<serde_json::Value>, // its shape varies with the kind of page
+    #[serde(skip)]
+    detected_language: OnceLock<Option<String>>, // filled in the first time detected_language is called
+}
+
+/// deserialize an optional image, keeping URLs but dropping inline "data:" images
+fn deserialize_image_url<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let image: Option<String> = Option::deserialize(deserializer)?;
+    Ok(image.filter(|image| !image.starts_with("data:")))
+}
+
+impl OrganicResult {
+
+    /// the link of the result parsed as a URL, see ResultLink::parsed
+    pub fn link_parsed(&self) -> Result<url::Url, url::ParseError> {
+        self.link.parsed()
+    }
+
+    /// the authority of the result's page according to scorer
+    pub fn estimated_page_authority(&self, scorer: &impl PageAuthority) -> Option<f32> {
+        scorer.score(self.link.as_str())
+    }
+
+    /// the schema.org type of the rich snippet, i.e. "Product", "Article" or "Recipe"
+    /// This looks for a "type", "@type" or "schema_type" key at the top of the rich snippet or one level down
+    pub fn schema_type(&self) -> Option<&str> {
+        fn type_of(value: &serde_json::Value) -> Option<&str> {
+            ["@type", "type", "schema_type"].iter()
+                .find_map(|key| value.get(key)?.as_str())
+        }
+        let rich_snippet = self.rich_snippet.as_ref()?;
+        type_of(rich_snippet).or_else(|| {
+            rich_snippet.as_object()?.values().find_map(type_of)
+        })
+    }
+
+    /// the language of the snippet according to detector
+    /// Detection only runs the first time this is called: later calls return the cached value
+    pub fn detected_language(&self, detector: &impl LanguageDetector) -> Option<&str> {
+        self.detected_language
+            .get_or_init(|| detector.detect(&self.snippet))
+            .as_deref()
+    }
+
+    /// the byte ranges of the snippet that match one of the snippet_matched phrases, sorted and merged
+    /// Matching ignores case in any script and every range starts and ends on a char boundary, so they are safe to slice with
+    pub fn snippet_match_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self.snippet_matched.iter().flatten()
+            .flat_map(|phrase| find_ignoring_case(&self.snippet, phrase))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// the snippet with every match wrapped in open and close, i.e. "<b>" and "</b>"
+    pub fn highlighted_snippet(&self, open: &str, close: &str) -> String {
+        let mut highlighted = String::with_capacity(self.snippet.len());
+        let mut last = 0;
+        for range in self.snippet_match_ranges() {
+            highlighted.push_str(&self.snippet[last..range.start]);
+            highlighted.push_str(open);
+            highlighted.push_str(&self.snippet[range.clone()]);
+            highlighted.push_str(close);
+            last = range.end;
+        }
+        highlighted.push_str(&self.snippet[last..]);
+        highlighted
+    }
+}
+
+/// the byte ranges of every occurrence of needle in haystack, comparing one lowercased char at a time
+/// Comparing chars rather than lowercased strings keeps the ranges valid for haystack even where lowercasing changes the length, i.e. "İ"
+fn find_ignoring_case(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+    let mut ranges = Vec::new();
+    for (start, _) in haystack.char_indices() {
+        let mut chars = haystack[start..].char_indices();
+        let matched = needle.iter().all(|&n| chars.next().is_some_and(|(_, c)| same(c, n)));
+        if matched {
+            let end = chars.next().map_or(haystack.len(), |(i, _)| start + i);
+            ranges.push(start..end);
+        }
+    }
+    ranges
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct AppResult {
+    pub title: String,
+    pub link: ResultLink,
+    pub platform: Option<String>, // i.e. "Google Play" or "App Store"
+    pub rating: Option<f64>,
+    pub reviews: Option<usize>,
+    pub price: Option<String>, // i.e. "Free" or "$4.99"
+    pub developer: Option<String>,
+    pub icon: Option<String>, // image URL
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct DictionaryResult {
+    pub word: String,
+    pub phonetic: Option<String>,
+    pub audio_link: Option<String>,
+    pub part_of_speech: Option<String>, // i.e. "noun"
+    #[serde(default)]
+    pub definitions: Vec<Definition>,
+    #[serde(default)]
+    pub synonyms: Vec<String>,
+    #[serde(default)]
+    pub antonyms: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct Definition {
+    pub definition: String,
+    pub example: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct WeatherResult {
+    pub temperature: f64,
+    pub unit: String, // "C" or "F"
+    pub condition: Option<String>, // i.e. "Partly cloudy"
+    pub humidity: Option<String>, // i.e. "62%"
+    pub wind_speed: Option<String>, // i.e. "8 mph"
+    pub wind_direction: Option<String>,
+    pub location: Option<String>,
+    pub date: Option<String>,
+    #[serde(default)]
+    pub forecast: Vec<WeatherForecastDay>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct WeatherForecastDay {
+    pub day: String, // i.e. "Tuesday"
+    pub condition: Option<String>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ConversionResult {
+    pub from_value: f64,
+    pub from_unit: String, // i.e. "US Dollar" or "Kilometer"
+    pub to_value: f64,
+    pub to_unit: String,
+    pub formula: Option<String>, // i.e. "divide the length value by 1.609"
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct CalculatorResult {
+    pub expression: String, // i.e. "2+2"
+    pub result: String, // i.e. "4"
+}
+
+impl CalculatorResult {
+
+    /// the result as a number, ignoring thousands separators. None if it is not a plain number
+    pub fn result_as_f64(&self) -> Option<f64> {
+        self.result.trim().replace(',', "").parse().ok()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct HotelResult {
+    pub name: String,
+    pub link: ResultLink,
+    pub rating: Option<f64>,
+    pub reviews: Option<usize>,
+    pub price_per_night: Option<String>, // i.e. "$189"
+    pub total_price: Option<String>,
+    #[serde(default)]
+    pub amenities: Vec<String>,
+    pub check_in: Option<String>,
+    pub check_out: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_image_url")]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub sponsored: bool,
+    pub source: Option<String>, // i.e. "Booking.com"
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct SportsResult {
+    pub title: String, // i.e. "NBA"
+    pub game_spotlight: Option<GameResult>, // the featured game
+    #[serde(default)]
+    pub games: Vec<GameResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct GameResult {
+    pub league: Option<String>,
+    pub date: Option<String>,
+    pub status: Option<String>, // i.e. "Final" or "Q3 4:12"
+    #[serde(default)]
+    pub teams: Vec<GameTeam>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct GameTeam {
+    pub name: String,
+    pub score: Option<String>, // a string since cricket and similar sports use scores like "245/6"
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct FinanceResult {
+    pub title: String, // i.e. "Apple Inc"
+    pub exchange: Option<String>, // i.e. "NASDAQ: AAPL"
+    pub stock_price: Option<f64>,
+    pub price_change: Option<f64>,
+    pub price_change_percent: Option<f64>,
+    pub currency: Option<String>, // i.e. "USD"
+    pub market_cap: Option<String>, // i.e. "2.39T"
+    pub pe_ratio: Option<f64>,
+    pub graph_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct TopStory {
+    pub link: ResultLink,
+    pub title: String,
+    pub visible_initially: bool,
+    pub source: String,
+    pub date: String,
+    pub date_utc: String,
+    pub block_position: usize,
+}
+
+impl TopStory {
+
+    /// the link of the story parsed as a URL, see ResultLink::parsed
+    pub fn link_parsed(&self) -> Result<url::Url, url::ParseError> {
+        self.link.parsed()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct TopProduct {
+    pub title: String,
+    pub price: String,
+    pub rating: f64,
+    pub reviews_count: Option<usize>,
+    pub sources: Vec<TopProductSource>,
+    pub specifications: Vec<TopProductSpecification>,
+    pub block_position: usize,
+}
+
+impl TopProduct {
+
+    /// the price as a number, i.e. 1049.0 for "$1,049.00"
+    /// Currency symbols and thousands separators are ignored. None if no number is found
+    pub fn price_value(&self) -> Option<f64> {
+        parse_price(&self.price)
+    }
+
+    /// the source with the lowest price, not counting shipping
+    pub fn cheapest_source(&self) -> Option<&TopProductSource> {
+        self.sources.iter()
+            .filter_map(|source| source.price().map(|price| (price, source)))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, source)| source)
+    }
+
+    /// map specification names to their values for quick lookup
+    pub fn spec_map(&self) -> HashMap<&str, &str> {
+        self.specifications.iter().map(|spec| spec.as_kv()).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct TopProductSource {
+    pub name: String,
+    pub link: ResultLink,
+    pub title: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct TopProductSpecification {
+    pub name: String,
+    pub value: String,
+}
+
+impl TopProductSource {
+
+    /// the price in the title, i.e. 119.99 for "$119.99 + $5.99 shipping at Best Buy"
+    pub fn price(&self) -> Option<f64> {
+        let base = self.title.split('+').next()?;
+        parse_price(base)
+    }
+
+    /// the shipping cost in the title, i.e. 5.99 for "$119.99 + $5.99 shipping at Best Buy"
+    pub fn shipping(&self) -> Option<f64> {
+        let (_, extra) = self.title.split_once('+')?;
+        if !extra.to_lowercase().contains("shipping") {
+            return None;
+        }
+        parse_price(extra)
+    }
+}
+
+/// the first number in a price string, i.e. 1049.0 for "$1,049.00"
+/// Currency symbols and thousands separators are ignored
+fn parse_price(price: &str) -> Option<f64> {
+    let number: String = price.chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .filter(|c| *c != ',')
+        .collect();
+    number.parse().ok()
+}
+
+impl TopProductSpecification {
+
+    /// this specification as a (name, value) pair
+    pub fn as_kv(&self) -> (&str, &str) {
+        (&self.name, &self.value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RelatedQuestion {
+    pub question: String,
+    pub answer: String,
+    pub source: RelatedQuestionSource,
+    pub block_position: usize,
+}
+
+impl RelatedQuestion {
+
+    /// the number of whitespace-separated words in the answer
+    pub fn answer_word_count(&self) -> usize {
+        self.answer.split_whitespace().count()
+    }
+
+    /// split the answer into sentences ending in '.', '!' or '?' followed by whitespace
+    /// The terminating punctuation is kept with each sentence
+    pub fn answer_sentences(&self) -> Vec<&str> {
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        let mut chars = self.answer.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if matches!(c, '.' | '!' | '?') {
+                if let Some((_, next)) = chars.peek() {
+                    if next.is_whitespace() {
+                        let end = i + c.len_utf8();
+                        let sentence = self.answer[start..end].trim();
+                        if !sentence.is_empty() {
+                            sentences.push(sentence);
+                        }
+                        start = end;
+                    }
+                }
+            }
+        }
+        let rest = self.answer[start..].trim();
+        if !rest.is_empty() {
+            sentences.push(rest);
+        }
+        sentences
+    }
+
+    /// a search for this question, with every other setting (location, device, hl etc.) copied from base
+    /// Search operator characters are stripped from the question, see strip_search_operators
+    pub fn to_params(&self, base: &Params) -> Params {
+        let mut params = base.clone();
+        params.q = strip_search_operators(&self.question);
+        params
+    }
+}
+
+/// remove characters Google would read as search operators: quotes, '*', '|', '~', brackets,
+/// and '-' or '+' at the start of a word, i.e. "What is -USB C?" becomes "What is USB C?"
+pub fn strip_search_operators(q: &str) -> String {
+    q.split(|c: char| c.is_whitespace() || c == '|')
+        .map(|word| word.trim_start_matches(['-', '+']))
+        .map(|word| word.replace(['"', '*', '~', '(', ')'], ""))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// a keyword prepared for comparison: whitespace collapsed and lowercased (in any script, per char::to_lowercase)
+/// On top of lowercasing, "ß" becomes "ss" and a final "ς" becomes "σ", so "Straße" and "STRASSE" or "ΟΔΟΣ" and "οδος" compare equal.
+/// This is not full Unicode case folding: other characters whose folding differs from their lowercase are left as lowercased
+pub fn normalize_keyword(keyword: &str) -> String {
+    let mut normalized = String::with_capacity(keyword.len());
+    for word in keyword.split_whitespace() {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        for c in word.chars().flat_map(char::to_lowercase) {
+            match c {
+                'ß' => normalized.push_str("ss"),
+                'ς' => normalized.push('σ'),
+                c => normalized.push(c),
+            }
+        }
+    }
+    normalized
+}
+
+/// the keywords without duplicates according to normalize_keyword, keeping the first spelling of each
+pub fn dedup_keywords<S: AsRef<str>>(keywords: &[S]) -> Vec<&str> {
+    let mut seen = std::collections::HashSet::new();
+    keywords.iter()
+        .map(|keyword| keyword.as_ref())
+        .filter(|keyword| seen.insert(normalize_keyword(keyword)))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RelatedQuestionSource {
+    pub link: ResultLink,
+    pub displayed_link: String,
+    pub title: String,
+}
+
+impl RelatedQuestionSource {
+
+    /// the link of the source parsed as a URL, see ResultLink::parsed
+    pub fn link_parsed(&self) -> Result<url::Url, url::ParseError> {
+        self.link.parsed()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RelatedSearch {
+    pub query: String,
+    pub link: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn async_url() {
+        let mut params = Params::new_env_usa("external ssd");
+        params.api_key = "demo".to_string();
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&location=United%20States&q=external%20ssd");
+        let params = params.with_async(true);
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search/async?api_key=demo&location=United%20States&q=external%20ssd");
+    }
+
+    #[test]
+    fn url_encoding_and_redaction() {
+        let params = Params::new("secret", "rock & roll bars", "München,Bavaria,Germany");
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=secret&location=M%C3%BCnchen,Bavaria,Germany&q=rock%20%26%20roll%20bars");
+        assert_eq!(params.redacted_url(), "https://api.scaleserp.com/search?api_key=***&location=M%C3%BCnchen,Bavaria,Germany&q=rock%20%26%20roll%20bars");
+    }
+
+    /// a deterministic stream of awkward strings: unicode, emoji, separators and escapes
+    fn awkward_strings(n: usize) -> Vec<String> {
+        let pool: Vec<char> = "aZ9 &=?#%+/\\,;:'\"<>[]{}|^`~!@$*()-_.éüßñ中文日本語🦀🎉\u{200b}\t\n".chars().collect();
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..n).map(|_| {
+            // xorshift, so the cases are the same on every run
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as usize
+            };
+            let len = next() % 24;
+            (0..len).map(|_| pool[next() % pool.len()]).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn plus_signs_are_encoded() {
+        let params = Params::new("demo", "c++ tutorial", "");
+        assert!(params.to_url().contains("q=c%2B%2B%20tutorial"));
+        let url = url::Url::parse(&params.to_url()).unwrap();
+        assert_eq!(url.query_pairs().find(|(key, _)| key == "q").unwrap().1, "c++ tutorial");
+    }
+
+    #[test]
+    fn to_url_round_trips_awkward_strings() {
+        let strings = awkward_strings(500);
+        for pair in strings.chunks(2) {
+            let params = Params::new("demo", &pair[0], "").with_location(&pair[1]);
+            let url = url::Url::parse(&params.to_url()).unwrap_or_else(|e| panic!("{:?} gave an invalid URL: {}", pair, e));
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            assert_eq!(query.len(), 3, "{:?}", pair);
+            assert_eq!(query["api_key"], "demo");
+            assert_eq!(query["q"], pair[0]);
+            assert_eq!(query["location"], pair[1]);
+        }
+    }
+
+    #[test]
+    fn params_from_env() {
+        let vars = |pairs: &[(&str, &str)]| {
+            let pairs: HashMap<String, String> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            move |name: &str| pairs.get(name).cloned()
+        };
+        match Params::from_vars("ssd", vars(&[("SCALE_SERP_LOCATION", "")])) {
+            Err(Error::MissingEnvVars(missing)) => assert_eq!(missing, vec!["SCALE_SERP_KEY", "SCALE_SERP_LOCATION"]),
+            _ => panic!("expected MissingEnvVars"),
+        }
+        let params = Params::from_vars("ssd", vars(&[
+            ("SCALE_SERP_KEY", "demo"),
+            ("SCALE_SERP_LOCATION", "United States"),
+            ("SCALE_SERP_DEVICE", "Mobile"),
+            ("SCALE_SERP_HL", "en"),
+            ("SCALE_SERP_GL", "us"),
+            ("SCALE_SERP_SAFE", "off"),
+            ("SCALE_SERP_NUM", "20"),
+        ])).unwrap();
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&location=United%20States&q=ssd&num=20&device=mobile&hl=en&gl=us&safe=off");
+        let invalid = Params::from_vars("ssd", vars(&[("SCALE_SERP_KEY", "demo"), ("SCALE_SERP_LOCATION", "us"), ("SCALE_SERP_NUM", "ten")]));
+        assert!(matches!(invalid, Err(Error::InvalidEnvVar { name, .. }) if name == "SCALE_SERP_NUM"));
+    }
+
+    #[test]
+    fn omitted_results() {
+        assert!(!fixture().has_omitted_results());
+        let mut raw = fixture_json();
+        raw["search_information"]["omitted_results"] = serde_json::json!("In order to show you the most relevant results, we have omitted some entries very similar to the 4 already displayed.");
+        let resp: Resp = serde_json::from_value(raw).unwrap();
+        assert!(resp.has_omitted_results());
+        let params = Params::new("demo", "ssd", "us").with_filter(DuplicateFilter::Disabled);
+        assert!(params.to_url().ends_with("&q=ssd&filter=0"));
+    }
+
+    #[test]
+    fn display_is_redacted() {
+        let params = Params::new("secret", "external ssd", "United States");
+        assert_eq!(params.to_string(), "GET https://api.scaleserp.com/search\n  api_key = ***\n  location = United States\n  q = external ssd");
+        assert_eq!(format!("{:?}", params), "Params(\"https://api.scaleserp.com/search?api_key=***&location=United%20States&q=external%20ssd\")");
+    }
+
+    #[test]
+    fn optional_location() {
+        let params = Params::new("demo", "external ssd", "");
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&q=external%20ssd");
+        let params = params.with_location("Boston").with_location_auto(false);
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&location=Boston&q=external%20ssd&location_auto=false");
+        assert_eq!(params.without_location().location, None);
+
+        let mut resp = fixture();
+        assert_eq!(resp.location_status(), LocationStatus::Used);
+        resp.search_metadata.location_auto_message = Some("Location could not be found".to_string());
+        assert_eq!(resp.location_status(), LocationStatus::FellBack);
+        resp.search_parameters.location = String::new();
+        assert_eq!(resp.location_status(), LocationStatus::NotRequested);
+        assert!(!resp.location_fell_back());
+    }
+
+    #[test]
+    fn search_type_url() {
+        let params = Params::new("demo", "external ssd", "United States").with_search_type(SearchType::News);
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&location=United%20States&q=external%20ssd&search_type=news");
+    }
+
+    #[test]
+    fn country_restrict_url() {
+        let params = Params::new("demo", "ssd", "").with_gl("us").with_cr(CountryRestrict::UnitedStates.as_str());
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&q=ssd&gl=us&cr=countryUS");
+        let params = params.with_cr(&format!("{}|{}", CountryRestrict::UnitedStates.as_str(), CountryRestrict::Canada.as_str()));
+        assert!(params.to_url().ends_with("&cr=countryUS%7CcountryCA"));
+    }
+
+    #[test]
+    fn as_sitesearch_url() {
+        let params = Params::new("demo", "external ssd", "").with_as_sitesearch("samsung.com");
+        assert_eq!(params.q, "external ssd");
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=demo&q=external%20ssd&as_sitesearch=samsung.com");
+    }
+
+    #[test]
+    fn truncate_query() {
+        let mut params = Params::new_env_usa("how long do external solid state drives last");
+        params.truncate_query(100);
+        assert_eq!(params.q, "how long do external solid state drives last");
+        params.truncate_query(24);
+        assert_eq!(params.q, "how long do external");
+        params.truncate_query(11);
+        assert_eq!(params.q, "how long do");
+        params.truncate_query(2);
+        assert_eq!(params.q, "ho");
+        let mut params = Params::new_env_usa("überraschung größe");
+        params.truncate_query(16);
+        assert_eq!(params.q, "überraschung");
+    }
+
+    fn related_question(answer: &str) -> RelatedQuestion {
+        RelatedQuestion {
+            question: "what is a surfactant?".to_string(),
+            answer: answer.to_string(),
+            source: RelatedQuestionSource {
+                link: "https://example.com/surfactants".into(),
+                displayed_link: "example.com".to_string(),
+                title: "Surfactants".to_string(),
+            },
+            block_position: 1,
+        }
+    }
+
+    fn fixture() -> Resp {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    /// the fixture as raw JSON, for tests that add or remove blocks
+    fn fixture_json() -> serde_json::Value {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn top_product_spec_map() {
+        let resp = fixture();
+        let product = &resp.top_products[0];
+        assert_eq!(product.specifications[0].as_kv(), ("Capacity", "1 TB"));
+        let specs = product.spec_map();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs.get("Interface"), Some(&"USB 3.2 Gen 2"));
+        assert_eq!(specs.get("Color"), None);
+    }
+
+    #[test]
+    fn missing_blocks_are_empty() {
+        let resp = fixture();
+        assert!(resp.had_ads_block() && resp.had_top_stories_block());
+        assert!(resp.had_top_products_block() && resp.had_related_questions_block());
+
+        let mut json = fixture_json();
+        json["ads"] = serde_json::json!([]);
+        json.as_object_mut().unwrap().remove("top_stories");
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        assert!(resp.ads.is_empty() && resp.had_ads_block());
+        assert!(resp.top_stories.is_empty() && !resp.had_top_stories_block());
+    }
+
+    #[test]
+    fn app_results() {
+        assert!(fixture().app_results.is_none());
+        let mut json = fixture_json();
+        json["app_results"] = serde_json::json!([{
+            "title": "Samsung Magician",
+            "link": "https://play.google.com/store/apps/details?id=com.samsung.magician",
+            "platform": "Google Play",
+            "rating": 4.1,
+            "reviews": 2210,
+            "price": "Free",
+            "developer": "Samsung Electronics Co., Ltd.",
+            "icon": "https://play-lh.googleusercontent.com/magician.png"
+        }]);
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let apps = resp.app_results.unwrap();
+        assert_eq!(apps[0].platform.as_deref(), Some("Google Play"));
+        assert_eq!(apps[0].reviews, Some(2210));
+    }
+
+    #[test]
+    fn dictionary_result() {
+        assert!(fixture().dictionary_result.is_none());
+        let mut json = fixture_json();
+        json["dictionary_result"] = serde_json::json!({
+            "word": "surfactant",
+            "phonetic": "/sərˈfaktənt/",
+            "part_of_speech": "noun",
+            "definitions": [
+                { "definition": "a substance which tends to reduce the surface tension of a liquid in which it is dissolved.", "example": "soap is a surfactant" }
+            ],
+            "synonyms": ["wetting agent"]
+        });
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let dictionary = resp.dictionary_result.unwrap();
+        assert_eq!(dictionary.word, "surfactant");
+        assert_eq!(dictionary.definitions[0].example.as_deref(), Some("soap is a surfactant"));
+        assert_eq!(dictionary.synonyms, vec!["wetting agent"]);
+        assert!(dictionary.antonyms.is_empty() && dictionary.audio_link.is_none());
+    }
+
+    #[test]
+    fn query_discrepancies() {
+        use QueryDiscrepancy::*;
+        assert_eq!(fixture().query_discrepancy(), None);
+        let cases = [
+            ("Taylor Swift", "taylor swift", CaseOnly),
+            ("what's up?", "whats up", PunctuationOnly),
+            ("zwiterionic surfactant", "zwitterionic surfactant", SpellingFix),
+            ("site:pcmag.com external ssd -hdd", "external ssd", OperatorRemoved),
+            ("external ssd", "portable solid state drive", Other),
+        ];
+        for (sent, displayed, expected) in cases {
+            assert_eq!(QueryDiscrepancy::classify(sent, displayed), Some(expected), "{} vs {}", sent, displayed);
+        }
+    }
+
+    #[test]
+    fn weather_result() {
+        assert!(fixture().weather_result.is_none());
+        let mut json = fixture_json();
+        json["weather_result"] = serde_json::json!({
+            "temperature": 77.0,
+            "unit": "F",
+            "condition": "Partly cloudy",
+            "humidity": "62%",
+            "wind_speed": "8 mph",
+            "location": "New York, NY",
+            "date": "Friday 2:00 PM",
+            "forecast": [
+                { "day": "Saturday", "condition": "Sunny", "high": 81.0, "low": 68.0 }
+            ]
+        });
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let weather = resp.weather_result.unwrap();
+        assert_eq!(weather.unit, "F");
+        assert_eq!(weather.forecast[0].high, Some(81.0));
+        assert!(weather.wind_direction.is_none());
+    }
+
+    #[test]
+    fn conversion_result() {
+        assert!(fixture().conversion_result.is_none());
+        let mut json = fixture_json();
+        json["conversion_result"] = serde_json::json!({
+            "from_value": 10.0,
+            "from_unit": "Kilometer",
+            "to_value": 6.21371,
+            "to_unit": "Mile",
+            "formula": "divide the length value by 1.609"
+        });
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let conversion = resp.conversion_result.unwrap();
+        assert_eq!((conversion.from_value, conversion.to_value), (10.0, 6.21371));
+        assert_eq!(conversion.to_unit, "Mile");
+    }
+
+    #[test]
+    fn calculator_result() {
+        assert!(fixture().calculator_result.is_none());
+        let mut json = fixture_json();
+        json["calculator_result"] = serde_json::json!({ "expression": "1200 * 3", "result": "3,600" });
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let calculator = resp.calculator_result.unwrap();
+        assert_eq!(calculator.expression, "1200 * 3");
+        assert_eq!(calculator.result_as_f64(), Some(3600.0));
+        let not_a_number = CalculatorResult { expression: "1/0".to_string(), result: "Infinity?".to_string() };
+        assert_eq!(not_a_number.result_as_f64(), None);
+    }
+
+    #[test]
+    fn hotel_results() {
+        assert!(fixture().hotel_results.is_none());
+        let mut json = fixture_json();
+        json["hotel_results"] = serde_json::json!([
+            {
+                "name": "The Standard, High Line",
+                "link": "https://www.standardhotels.com/new-york/properties/high-line",
+                "rating": 4.4,
+                "reviews": 3120,
+                "price_per_night": "$389",
+                "total_price": "$778",
+                "amenities": ["Free Wi-Fi", "Gym"],
+                "check_in": "2022-07-15",
+                "check_out": "2022-07-17",
+                "thumbnail": "data:image/png;base64,iVBORw0KGgo",
+                "sponsored": true,
+                "source": "Expedia"
+            },
+            { "name": "Pod 51", "link": "https://www.thepodhotel.com/pod-51" }
+        ]);
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let hotels = resp.hotel_results.unwrap();
+        assert_eq!(hotels[0].amenities, vec!["Free Wi-Fi", "Gym"]);
+        assert!(hotels[0].sponsored && hotels[0].thumbnail.is_none());
+        assert!(!hotels[1].sponsored && hotels[1].rating.is_none());
+    }
+
+    #[test]
+    fn expanded_ad_sitelinks() {
+        let resp = fixture();
+        let simple = resp.ads[0].sitelinks.as_ref().unwrap();
+        assert!(simple[0].description.is_none() && simple[0].links.is_none());
+        let expanded = resp.ads[1].sitelinks.as_ref().unwrap();
+        assert_eq!(expanded[0].description.as_deref(), Some("Rugged, IP65 rated storage built for adventure."));
+        let nested = expanded[0].links.as_ref().unwrap();
+        assert_eq!(nested[1].title, "2TB");
+    }
+
+    #[test]
+    fn sports_results() {
+        assert!(fixture().sports_results.is_none());
+        let mut json = fixture_json();
+        json["sports_results"] = serde_json::json!({
+            "title": "NBA",
+            "game_spotlight": {
+                "league": "NBA",
+                "date": "Today, 7:30 PM",
+                "status": "Final",
+                "teams": [
+                    { "name": "Knicks", "score": "112" },
+                    { "name": "Celtics", "score": "108" }
+                ]
+            },
+            "games": [
+                { "status": "Q3 4:12", "teams": [{ "name": "Lakers", "score": "77" }, { "name": "Suns", "score": "80" }] }
+            ]
+        });
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let sports = resp.sports_results.unwrap();
+        let spotlight = sports.game_spotlight.unwrap();
+        assert_eq!(spotlight.teams[0].score.as_deref(), Some("112"));
+        assert_eq!(sports.games[0].status.as_deref(), Some("Q3 4:12"));
+        assert!(sports.games[0].league.is_none());
+    }
+
+    #[test]
+    fn finance_result() {
+        assert!(fixture().finance_result.is_none());
+        let mut json = fixture_json();
+        json["finance_result"] = serde_json::json!({
+            "title": "Apple Inc",
+            "exchange": "NASDAQ: AAPL",
+            "stock_price": 147.04,
+            "price_change": -1.67,
+            "price_change_percent": -1.12,
+            "currency": "USD",
+            "market_cap": "2.38T",
+            "pe_ratio": 24.38
+        });
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let finance = resp.finance_result.unwrap();
+        assert_eq!(finance.exchange.as_deref(), Some("NASDAQ: AAPL"));
+        assert_eq!(finance.price_change, Some(-1.67));
+        assert!(finance.graph_url.is_none());
+    }
+
+    #[test]
+    fn schema_type() {
+        let mut resp = fixture();
+        assert_eq!(resp.organic_results[0].schema_type(), None);
+        resp.organic_results[0].rich_snippet = Some(serde_json::json!({ "@type": "Article" }));
+        resp.organic_results[1].rich_snippet = Some(serde_json::json!({
+            "top": { "type": "Product", "detected_extensions": { "rating": 4.8 } }
+        }));
+        resp.organic_results[2].rich_snippet = Some(serde_json::json!({ "bottom": { "extensions": ["Rating: 4.5"] } }));
+        assert_eq!(resp.organic_results[0].schema_type(), Some("Article"));
+        assert_eq!(resp.organic_results[1].schema_type(), Some("Product"));
+        assert_eq!(resp.organic_results[2].schema_type(), None);
+    }
+
+    #[test]
+    fn product_sources() {
+        let resp = fixture();
+        let t7 = &resp.top_products[0];
+        assert_eq!(t7.sources[0].price(), Some(109.99));
+        assert_eq!(t7.sources[0].shipping(), None);
+        assert_eq!(t7.sources[1].price(), Some(119.99));
+        assert_eq!(t7.sources[1].shipping(), Some(5.99));
+        assert_eq!(t7.cheapest_source().unwrap().name, "Amazon.com");
+
+        let by_merchant = resp.products_by_merchant();
+        assert_eq!(by_merchant.len(), 3);
+        assert_eq!(by_merchant["B&H Photo"][0].title, "SanDisk Extreme Portable SSD 2TB");
+    }
+
+    #[test]
+    fn organic_results_sorted_by_domain() {
+        let resp = fixture();
+        let domains: Vec<(&str, usize)> = resp.organic_results_sorted_by_domain().iter()
+            .map(|result| (result.domain.as_str(), result.position))
+            .collect();
+        assert_eq!(domains, vec![
+            ("www.pcmag.com", 1),
+            ("www.samsung.com", 2),
+            ("samsung.com", 4),
+            ("www.tomshardware.com", 3),
+        ]);
+    }
+
+    #[test]
+    fn question_coverage() {
+        let resp = fixture();
+        let coverage = resp.question_coverage(&["is an external ssd  worth it", "How long do external SSDs last?", "Are SSDs faster than HDDs?"]);
+        assert_eq!(coverage.len(), 3);
+        assert!(coverage["is an external ssd  worth it"]);
+        assert!(coverage["How long do external SSDs last?"]);
+        assert!(!coverage["Are SSDs faster than HDDs?"]);
+    }
+
+    #[test]
+    fn top_stories_by_source() {
+        let resp = fixture();
+        assert_eq!(resp.unique_news_sources(), vec!["The Verge", "PCMag"]);
+        let grouped = resp.top_stories_by_source();
+        assert_eq!(grouped.len(), 2);
+        let verge: Vec<&str> = grouped["The Verge"].iter().map(|story| story.title.as_str()).collect();
+        assert_eq!(verge, vec!["The best portable SSDs of 2022", "Samsung's T7 Shield is a rugged SSD"]);
+        assert_eq!(grouped["PCMag"].len(), 1);
+    }
+
+    #[test]
+    fn query_rewritten_warning() {
+        let displayed = |sent: &str, displayed: &str| {
+            let mut json = fixture_json();
+            json["search_parameters"]["q"] = sent.into();
+            json["search_information"]["query_displayed"] = displayed.into();
+            serde_json::from_value::<Resp>(json).unwrap().warnings()
+        };
+        assert!(displayed("external ssd", "External SSD").is_empty());
+        assert_eq!(displayed("extrenal ssd", "external ssd"), vec![SerpWarning::QueryRewritten(QueryDiscrepancy::SpellingFix)]);
+        assert_eq!(displayed("ssd site:pcmag.com", "ssd"), vec![SerpWarning::QueryRewritten(QueryDiscrepancy::OperatorRemoved)]);
+        assert_eq!(displayed("external ssd", "portable hard drive"), vec![SerpWarning::QueryRewritten(QueryDiscrepancy::Other)]);
+    }
+
+    #[test]
+    fn consent_wall_warning() {
+        let wall: Resp = serde_json::from_str(include_str!("../fixtures/search_consent_wall.json")).unwrap();
+        assert_eq!(wall.warnings(), vec![SerpWarning::ProbableConsentWall]);
+        assert!(fixture().warnings().is_empty());
+
+        // a query that really has no results is not flagged
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../fixtures/search_consent_wall.json")).unwrap();
+        json["search_information"]["query_displayed"] = "externe ssd".into();
+        let no_results: Resp = serde_json::from_value(json.clone()).unwrap();
+        assert!(no_results.warnings().is_empty());
+        json["search_information"]["query_displayed"] = "".into();
+        json["search_information"]["original_query_yields_zero_results"] = true.into();
+        assert!(serde_json::from_value::<Resp>(json.clone()).unwrap().warnings().is_empty());
+
+        // nor is an empty-looking page that still has features
+        json["search_information"]["original_query_yields_zero_results"] = false.into();
+        json["related_searches"] = serde_json::json!([{ "query": "ssd extern", "link": "https://www.google.de/search?q=ssd+extern" }]);
+        assert!(serde_json::from_value::<Resp>(json).unwrap().warnings().is_empty());
+    }
+
+    #[test]
+    fn organic_results_with_date() {
+        assert!(fixture().organic_results_with_date().is_empty());
+        let mut json = fixture_json();
+        json["organic_results"][1]["date"] = "Jul 6, 2022".into();
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let dated = resp.organic_results_with_date();
+        assert_eq!(dated.len(), 1);
+        assert_eq!((dated[0].position, dated[0].date.as_deref()), (2, Some("Jul 6, 2022")));
+    }
+
+    #[test]
+    fn organic_result_at() {
+        let mut resp = fixture();
+        assert_eq!(resp.organic_result_at(3).unwrap().domain, "www.tomshardware.com");
+        resp.organic_results.remove(0);
+        assert!(resp.organic_result_at(1).is_none());
+        assert_eq!(resp.organic_result_at(2).unwrap().domain, "www.samsung.com");
+    }
+
+    #[test]
+    fn cached_links_and_thumbnails() {
+        let resp = fixture();
+        assert_eq!(resp.cached_links(), vec![
+            "https://webcache.googleusercontent.com/search?q=cache:abc123:https://www.pcmag.com/picks/the-best-external-ssds",
+            "https://webcache.googleusercontent.com/search?q=cache:def456:https://www.tomshardware.com/reviews/best-external-drives,5340.html",
+        ]);
+        assert_eq!(resp.organic_results[1].thumbnail.as_deref(), Some("https://images.samsung.com/us/t7/thumb.jpg"));
+        assert_eq!(resp.organic_results[2].thumbnail, None); // inline base64 is dropped
+    }
+
+    #[test]
+    fn organic_rank() {
+        let resp = fixture();
+        assert_eq!(resp.organic_rank("https://www.tomshardware.com/reviews/best-external-drives,5340.html"), Some(3));
+        assert_eq!(resp.organic_rank("https://www.tomshardware.com/reviews/"), None);
+        assert_eq!(resp.organic_rank("http://www.pcmag.com/picks/the-best-external-ssds"), None);
+    }
+
+    #[test]
+    fn contains_domain() {
+        let resp = fixture();
+        assert!(resp.contains_domain("pcmag.com")); // organic
+        assert!(resp.contains_domain("www.newegg.com")); // ads
+        assert!(resp.contains_domain("amazon.com")); // top product sources
+        assert!(!resp.contains_domain("example.com"));
+    }
+
+    #[test]
+    fn organic_result_count_for_domain() {
+        let resp = fixture();
+        assert_eq!(resp.organic_result_count_for_domain("samsung.com"), 2); // "www.samsung.com" and "samsung.com"
+        assert_eq!(resp.organic_result_count_for_domain("WWW.Samsung.com."), 2);
+        assert_eq!(resp.organic_result_count_for_domain("pcmag.com"), 1);
+        assert_eq!(resp.organic_result_count_for_domain("bestbuy.com"), 0); // only an ad
+        assert_eq!(resp.organic_result_count_for_domain(""), 0);
+    }
+
+    #[test]
+    fn products_in_price_range() {
+        let resp = fixture();
+        let products = &resp.top_products;
+        assert_eq!(products[0].price_value(), Some(109.99));
+        assert_eq!(products[1].price_value(), Some(1049.0));
+        let in_range: Vec<&str> = resp.products_in_price_range(100.0, 500.0).iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(in_range, vec!["Samsung T7 Portable SSD 1TB"]);
+        assert_eq!(resp.products_in_price_range(0.0, 2000.0).len(), 2);
+        assert!(resp.products_in_price_range(0.0, 50.0).is_empty());
+    }
+
+    #[test]
+    fn best_products() {
+        let mut resp = fixture();
+        assert_eq!(resp.highest_rated_product().unwrap().title, "Samsung T7 Portable SSD 1TB");
+        assert_eq!(resp.most_reviewed_product().unwrap().title, "SanDisk Extreme Portable SSD 2TB");
+        resp.top_products.clear();
+        assert!(resp.highest_rated_product().is_none());
+        assert!(resp.most_reviewed_product().is_none());
+    }
+
+    #[test]
+    fn language_mismatches() {
+        let mut resp = fixture();
+        resp.organic_results[1].snippet = "Die Samsung T7 ist eine schnelle und robuste externe SSD.".to_string();
+        resp.organic_results[2].snippet = "高速なポータブルSSDです。".to_string();
+        struct Detector {
+            calls: std::cell::Cell<usize>,
+        }
+        impl LanguageDetector for Detector {
+            fn detect(&self, text: &str) -> Option<String> {
+                self.calls.set(self.calls.get() + 1);
+                if text.chars().any(|c| ('\u{3040}'..='\u{30ff}').contains(&c)) {
+                    Some("ja".to_string())
+                } else if text.contains(" und ") {
+                    Some("de".to_string())
+                } else {
+                    Some("en".to_string())
+                }
+            }
+        }
+        let detector = Detector { calls: std::cell::Cell::new(0) };
+        let mismatches: Vec<usize> = resp.language_mismatches("en", &detector).iter().map(|r| r.position).collect();
+        assert_eq!(mismatches, vec![2, 3]);
+        assert_eq!(resp.organic_results[2].detected_language(&detector), Some("ja"));
+        // every snippet was detected exactly once
+        assert_eq!(detector.calls.get(), resp.organic_results.len());
+    }
+
+    #[test]
+    fn related_question_answer_helpers() {
+        let rq = related_question("Surfactants lower surface tension. They are used in soaps!  Are they safe? Mostly, at 3.5 ppm or less");
+        assert_eq!(rq.answer_word_count(), 18);
+        assert_eq!(rq.answer_sentences(), vec![
+            "Surfactants lower surface tension.",
+            "They are used in soaps!",
+            "Are they safe?",
+            "Mostly, at 3.5 ppm or less",
+        ]);
+        assert!(related_question("").answer_sentences().is_empty());
+    }
+    #[tokio::test]
+    #[ignore] // calls the live API: needs SCALE_SERP_KEY and network access
+    async fn quick_demo() {
+        let params = Params::new_env_nyc("anionic surfactants");
+        let body: Resp = reqwest::get(&params.to_url())
+            .await.unwrap()
+            .json()
+            .await.unwrap();
+        println!("body = {:?}", body);
+    }
+    #[tokio::test]
+    #[ignore] // calls the live API: needs SCALE_SERP_KEY and network access
+    async fn search_with_top_products() {
+        // This search should give some top products: no all searches do
+        let params = Params::new_env_nyc("external ssd");
+        let body: Resp = reqwest::get(&params.to_url())
+            .await.unwrap()
+            .json()
+            .await.unwrap();
+        assert!(!body.top_products.is_empty());
+        println!("top_products = {:?}", body.top_products);
+    }
+    #[tokio::test]
+    #[ignore] // calls the live API: needs SCALE_SERP_KEY and network access
+    async fn search_with_top_ads() {
+        // This search should give some ads: no all searches do
+        // NOTE: this test still seems to fail from time to time, perhaps something with the online bidding
+        let params = Params::new_env("timeshare", "Chicago,Illinois,United States");
+        let body: Resp = reqwest::get(&params.to_url())
+            .await.unwrap()
+            .json()
+            .await.unwrap();
+        assert!(!body.ads.is_empty());
+        println!("ads = {:?}", body.ads);
+    }
+    #[tokio::test]
+    #[ignore] // calls the live API: needs SCALE_SERP_KEY and network access
+    async fn common_and_uncommon() {
+        // Some properties like top_stories appear for some searches but not for others
+        // This test picks several phrases: some common, some obscure, some misspelled in an attempt to be robust against missing fields
+        for phrase in ["Taylor Swift", "how to prep to paint", "zwitterionic surfactant"].iter() {
+            println!("testing '{}'", &phrase);
+            let params = Params::new_env_nyc(phrase);
+            let body: Resp = reqwest::get(&params.to_url())
+                .await.unwrap()
+                .json()
+                .await.unwrap();
+            println!("body = {:?}", body);
+        }
+    }
+
+    #[test]
+    fn unmodeled_blocks() {
+        let mut raw = fixture_json();
+        assert!(Resp::unmodeled_blocks(&raw).is_empty());
+        raw["pagination"] = serde_json::json!({"current": 1});
+        raw["inline_images"] = serde_json::json!([]);
+        let mut unmodeled = Resp::unmodeled_blocks(&raw);
+        unmodeled.sort();
+        assert_eq!(unmodeled, vec!["inline_images", "pagination"]);
+        assert!(Resp::unmodeled_blocks(&serde_json::json!("not an object")).is_empty());
+    }
+
+    #[test]
+    fn skip_on_incident() {
+        let params = Params::new("demo", "external ssd", "United States").with_skip_on_incident(SkipOnIncident::AllIncidents);
+        assert!(params.to_url().ends_with("&q=external%20ssd&skip_on_incident=all_incidents"));
+
+        let skipped: SearchOutcome = serde_json::from_str(include_str!("../fixtures/search_skipped_incident.json")).unwrap();
+        match &skipped {
+            SearchOutcome::SkippedIncident(skip) => assert!(skip.message.starts_with("Request skipped as a Google incident")),
+            other => panic!("expected a skip, got {:?}", other),
+        }
+        assert!(skipped.completed().is_none());
+
+        let completed: SearchOutcome = serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap();
+        assert!(!completed.is_skipped());
+        assert_eq!(completed.completed().unwrap().organic_results.len(), 4);
+    }
+
+    #[test]
+    fn estimated_total_pages() {
+        let mut resp = fixture();
+        let params = Params::new("demo", "external ssd", "United States");
+        assert_eq!(resp.estimated_total_pages(&params), 10); // millions of results, but capped
+        assert_eq!(resp.estimated_total_pages(&params.clone().with_num(30)), 4);
+        assert!(params.clone().with_num(30).to_url().ends_with("&num=30"));
+        resp.search_information.total_results = 25;
+        assert_eq!(resp.estimated_total_pages(&params), 3);
+        resp.search_information.total_results = 0;
+        assert_eq!(resp.estimated_total_pages(&params), 0);
+    }
+
+    #[test]
+    fn organic_snippets() {
+        let resp = fixture();
+        let snippets = resp.organic_snippets();
+        assert_eq!(snippets.len(), resp.organic_results.len());
+        assert_eq!(snippets[0], resp.organic_results[0].snippet);
+    }
+
+    #[test]
+    fn ads_above_and_below_fold() {
+        let resp = fixture();
+        let above: Vec<&str> = resp.ads_above_fold().iter().map(|ad| ad.domain.as_str()).collect();
+        let below: Vec<&str> = resp.ads_below_fold().iter().map(|ad| ad.domain.as_str()).collect();
+        assert_eq!(above, vec!["www.bestbuy.com", "www.samsung.com"]);
+        assert_eq!(below, vec!["www.newegg.com"]);
+    }
+
+    #[test]
+    fn vertical_response_without_organic_results() {
+        let mut raw = fixture_json();
+        let object = raw.as_object_mut().unwrap();
+        object.remove("organic_results");
+        object.remove("related_searches");
+        object.insert("news_results".to_string(), serde_json::json!([{"position": 1, "title": "SSD prices fall"}]));
+        let resp: Resp = serde_json::from_value(raw.clone()).unwrap();
+        assert!(resp.organic_results.is_empty());
+        assert_eq!(crate::vertical::check(&raw, Some(SearchType::News)), None);
+    }
+
+    #[test]
+    fn engine_url_parsed() {
+        let url = fixture().search_metadata.engine_url_parsed().unwrap();
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(url.host_str(), Some("www.google.com"));
+        assert_eq!(query["q"], "external ssd");
+        assert_eq!(query["gl"], "us");
+    }
+
+    #[test]
+    fn follow_up_searches() {
+        let base = Params::new("demo", "external ssd", "United States").with_device(Device::Mobile).with_hl("en");
+        let searches = fixture().follow_up_searches(&base);
+        let queries: Vec<&str> = searches.iter().map(|params| params.q.as_str()).collect();
+        assert_eq!(queries, vec!["Is an external SSD worth it?", "How long do external SSDs last?"]);
+        assert!(searches.iter().all(|params| params.device == Some(Device::Mobile) && params.location.as_deref() == Some("United States")));
+        assert_eq!(strip_search_operators("\"best\" ssd -sata +nvme (2tb|4tb) ~fast* - "), "best ssd sata nvme 2tb 4tb fast");
+    }
+
+    #[test]
+    fn page_authority() {
+        let scores: HashMap<&str, f32> = HashMap::from([("https://www.pcmag.com/picks/the-best-external-ssds", 91.0)]);
+        let scorer = |url: &str| scores.get(url).copied();
+        let resp = fixture();
+        let authorities: Vec<Option<f32>> = resp.organic_results.iter().map(|result| result.estimated_page_authority(&scorer)).collect();
+        assert_eq!(authorities[0], Some(91.0));
+        assert!(authorities[1..].iter().all(|authority| authority.is_none()));
+    }
+
+    #[test]
+    fn word_cloud_data() {
+        let mut resp = fixture();
+        resp.organic_results.truncate(2);
+        resp.organic_results[0].snippet = "The best SSD is the 'fastest' SSD, and it's portable.".to_string();
+        resp.organic_results[1].snippet = "Portable SSDs: the best of 2024".to_string();
+        let counts = resp.word_cloud_data(None);
+        assert_eq!(counts["ssd"], 2);
+        assert_eq!(counts["best"], 2);
+        assert_eq!(counts["fastest"], 1);
+        assert_eq!(counts["it's"], 1);
+        assert_eq!(counts["2024"], 1);
+        assert!(!counts.contains_key("the") && !counts.contains_key("is"));
+
+        let options = WordCloudOptions { stop_words: vec!["ssd".to_string()] };
+        let counts = resp.word_cloud_data(Some(&options));
+        assert!(!counts.contains_key("ssd"));
+        assert_eq!(counts["the"], 3);
+    }
+
+    #[test]
+    fn non_latin_queries() {
+        for q in ["قرص صلب خارجي", "כונן חיצוני", "外付け SSD", "외장 SSD"] {
+            let params = Params::new("secret", q, "");
+            let url = url::Url::parse(&params.to_url()).unwrap();
+            let sent = url.query_pairs().find(|(key, _)| key == "q").unwrap().1;
+            assert_eq!(sent, q);
+            assert!(params.to_url().is_ascii());
+        }
+        let mut params = Params::new("secret", "外付け SSD 1TB おすすめ", "");
+        params.truncate_query(8);
+        assert_eq!(params.q, "外付け SSD");
+    }
+
+    #[test]
+    fn highlighted_snippet() {
+        let mut result = fixture().organic_results.remove(0);
+        result.snippet = "Die beste externe SSD für İstanbul: EXTERNE SSDs im Test".to_string();
+        // "İ" lowercases to two chars, so a lowercased "i̇stanbul" does not match it, but must not panic either
+        result.snippet_matched = Some(vec!["externe ssd".to_string(), "i̇stanbul".to_string()]);
+        assert_eq!(
+            result.highlighted_snippet("<b>", "</b>"),
+            "Die beste <b>externe SSD</b> für İstanbul: <b>EXTERNE SSD</b>s im Test"
+        );
+
+        result.snippet = "أفضل قرص صلب خارجي لعام 2024 — قرص صلب".to_string();
+        result.snippet_matched = Some(vec!["قرص صلب".to_string(), "صلب خارجي".to_string()]);
+        assert_eq!(result.highlighted_snippet("[", "]"), "أفضل [قرص صلب خارجي] لعام 2024 — [قرص صلب]");
+        for range in result.snippet_match_ranges() {
+            assert!(result.snippet.is_char_boundary(range.start) && result.snippet.is_char_boundary(range.end));
+        }
+
+        result.snippet_matched = None;
+        assert_eq!(result.highlighted_snippet("[", "]"), result.snippet);
+    }
+
+    #[test]
+    fn keyword_dedup() {
+        assert_eq!(normalize_keyword("  Straße   Berlin "), "strasse berlin");
+        assert_eq!(normalize_keyword("ΟΔΟΣ"), normalize_keyword("οδοσ"));
+        assert_eq!(normalize_keyword("ПРИВЕТ мир"), "привет мир");
+        let keywords = ["Straße", "STRASSE", "東京 ホテル", "東京  ホテル", "ΟΔΟΣ", "οδός", "οδοσ"];
+        assert_eq!(dedup_keywords(&keywords), vec!["Straße", "東京 ホテル", "ΟΔΟΣ", "οδός"]);
+    }
+
+    #[test]
+    fn parsed_links() {
+        let resp = fixture();
+        let result = &resp.organic_results[0];
+        let url = result.link_parsed().unwrap();
+        assert_eq!(url.as_str(), result.link.as_str());
+        assert!(url.host_str().is_some());
+        assert!(resp.ads[0].link_parsed().is_ok());
+        assert!(resp.top_stories[0].link_parsed().is_ok());
+        assert!(resp.related_questions[0].source.link_parsed().is_ok());
+
+        let mut json = fixture_json();
+        json["organic_results"][0]["link"] = "https://example.com/a/b?id=7#reviews".into();
+        json["organic_results"][1]["link"] = "not a url".into();
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        let url = resp.organic_results[0].link_parsed().unwrap();
+        assert_eq!(url.path_segments().unwrap().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(url.query_pairs().next().unwrap().1, "7");
+        assert_eq!(url.fragment(), Some("reviews"));
+        assert_eq!(resp.organic_results[1].link_parsed(), Err(url::ParseError::RelativeUrlWithoutBase));
+    }
+
+    #[test]
+    fn with_query() {
+        let query = crate::operators::site_search("example.com", "pricing").exclude("login");
+        let params = Params::new("secret", "", "").with_query(&query).unwrap();
+        assert_eq!(params.q, "site:example.com pricing -login");
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=secret&q=site:example.com%20pricing%20-login");
+        assert!(Params::new("secret", "ssd", "").with_query(&QueryBuilder::new()).is_err());
+    }
+
+    #[test]
+    fn auto_location() {
+        let params = Params::new("secret", "coffee near me", "Boston,Massachusetts,United States").with_auto_location();
+        assert_eq!(params.location, None);
+        assert_eq!(params.to_url(), "https://api.scaleserp.com/search?api_key=secret&q=coffee%20near%20me&location_auto=true");
+        assert!(params.validate().is_ok());
+
+        let params = params.with_location("Boston");
+        assert!(matches!(params.validate(), Err(Error::InvalidConfig(_))));
+        assert!(params.with_location_auto(false).validate().is_ok());
+    }
+
+    #[test]
+    fn ad_keywords() {
+        let resp = fixture();
+        let keywords = resp.ad_keywords();
+        assert_eq!(&keywords[..5], &["Portable", "SSDs", "Fast", "Free", "Shipping"]);
+
+        let mut json = fixture_json();
+        json["ads"][0]["highlights"] = serde_json::json!(["external SSD", "Free shipping"]);
+        json["ads"][1]["description_matched"] = serde_json::json!(["external SSD"]);
+        json["ads"].as_array_mut().unwrap().truncate(2);
+        let resp: Resp = serde_json::from_value(json).unwrap();
+        assert_eq!(resp.ad_keywords(), vec!["external SSD", "Free shipping"]);
+    }
+
+    #[test]
+    fn effective_request_listing() {
+        let vars: HashMap<&str, &str> = [("SCALE_SERP_KEY", "secret"), ("SCALE_SERP_LOCATION", "United States"), ("SCALE_SERP_HL", "en")].into();
+        let params = Params::from_vars("rock & roll", |name| vars.get(name).map(|v| v.to_string())).unwrap()
+            .with_device(Device::Mobile)
+            .with_num(20);
+        let client = crate::client::ScaleSerpClient::builder()
+            .api_key("secret")
+            .dialect(crate::dialect::ResponseDialect::Latest)
+            .build()
+            .unwrap();
+        assert_eq!(client.describe_request(&params), "\
+GET https://api.scaleserp.com/search
+  api_key = ***
+  location = United States
+  q = rock & roll
+  num = 20
+  device = mobile
+  hl = en
+  hide_base64_images = true");
+        assert!(params.to_string().ends_with("  hl = en"));
+        assert!(!client.describe_request(&params).contains("secret"));
+        let pairs = client.effective_pairs(&params);
+        assert_eq!(pairs[0], ("api_key", "secret".to_string()));
+        assert_eq!(pairs.len(), params.effective_pairs().len() + 1);
+    }
+
+    #[test]
+    fn layout_signature() {
+        let resp = fixture();
+        assert_eq!(resp.layout_signature(), "A2 O1 P2 S3 Q2 O3 A1");
+        assert_eq!(resp.layout_change(&fixture()), None);
+
+        let mut json = fixture_json();
+        for story in json["top_stories"].as_array_mut().unwrap() {
+            story["block_position"] = 0.into();
+        }
+        json["ads"][2]["block_position"] = "top".into();
+        let moved: Resp = serde_json::from_value(json).unwrap();
+        assert_eq!(moved.layout_signature(), "A3 S3 O1 P2 Q2 O3");
+        assert_eq!(
+            moved.layout_change(&resp),
+            Some(LayoutChange { before: "A2 O1 P2 S3 Q2 O3 A1".to_string(), after: "A3 S3 O1 P2 Q2 O3".to_string() })
+        );
+    }
+
+    #[test]
+    fn into_json_value() {
+        let resp = fixture();
+        let value = serde_json::Value::from(&resp);
+        assert_eq!(value["search_parameters"]["q"], "external ssd");
+        assert_eq!(value["organic_results"].as_array().unwrap().len(), resp.organic_results.len());
+        let back: Resp = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::Value::from(&back), value);
+    }
+}
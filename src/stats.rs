@@ -0,0 +1,226 @@
+use std::collections::{BTreeSet, HashMap};
+use serde::Serialize;
+use crate::features::SerpFeatures;
+use crate::link::normalize_domain;
+use crate::search::Resp;
+
+/// how many domains CorpusStats lists
+pub const TOP_DOMAINS: usize = 20;
+/// how many distinct domains an Accumulator tracks by default
+pub const DEFAULT_DOMAIN_CAPACITY: usize = 10_000;
+
+/// Aggregates over many responses, i.e. a month of stored searches
+/// A response counts as having a feature by the same rule as SerpFeatures::of: its block is there and not empty.
+/// There is no share with a knowledge graph, as Resp does not model that block (see schema::supported_blocks)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CorpusStats {
+    pub responses: usize,
+    pub average_ads: f64, // ads per response
+    pub share_with_ads: f64, // the fraction of responses with ads, from 0 to 1
+    pub share_with_top_stories: f64,
+    pub share_with_top_products: f64,
+    pub share_with_related_questions: f64,
+    pub top_domains: Vec<DomainStats>, // the TOP_DOMAINS domains with the most organic results, most first
+}
+
+/// How often one domain ranked organically across a corpus
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DomainStats {
+    pub domain: String, // normalized, see normalize_domain
+    pub appearances: usize, // organic results on the domain, possibly up to overcount too many
+    pub overcount: usize, // appearances inherited from domains it replaced once the accumulator was full, 0 if exact
+    pub average_position: f64, // over the appearances that were actually seen
+}
+
+/// Builds CorpusStats one response at a time, so a corpus never has to be in memory at once
+/// At most domain_capacity domains are tracked. Once full, a new domain replaces the least seen one and takes over its count
+/// (the Space-Saving algorithm), so a domain is never undercounted and any domain in more than 1/domain_capacity
+/// of all organic results is sure to be listed. DomainStats::overcount says how much of a count may have been inherited
+pub struct Accumulator {
+    responses: usize,
+    ads: usize,
+    with_ads: usize,
+    with_top_stories: usize,
+    with_top_products: usize,
+    with_related_questions: usize,
+    domains: HashMap<String, DomainTally>,
+    by_appearances: BTreeSet<(usize, String)>, // the same domains ordered by appearances, to find the least seen quickly
+    domain_capacity: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct DomainTally {
+    appearances: usize,
+    overcount: usize,
+    position_sum: usize, // over the appearances that were actually seen
+}
+
+impl Accumulator {
+
+    /// an empty accumulator that tracks up to DEFAULT_DOMAIN_CAPACITY domains
+    pub fn new() -> Self {
+        Accumulator::with_domain_capacity(DEFAULT_DOMAIN_CAPACITY)
+    }
+
+    /// an empty accumulator that tracks up to domain_capacity domains
+    pub fn with_domain_capacity(domain_capacity: usize) -> Self {
+        Accumulator {
+            responses: 0,
+            ads: 0,
+            with_ads: 0,
+            with_top_stories: 0,
+            with_top_products: 0,
+            with_related_questions: 0,
+            domains: HashMap::new(),
+            by_appearances: BTreeSet::new(),
+            domain_capacity: domain_capacity.max(1),
+        }
+    }
+
+    /// add one response to the totals
+    pub fn add(&mut self, resp: &Resp) {
+        self.responses += 1;
+        self.ads += resp.ads.len();
+        let features = SerpFeatures::of(resp);
+        self.with_ads += features.contains(SerpFeatures::ADS) as usize;
+        self.with_top_stories += features.contains(SerpFeatures::TOP_STORIES) as usize;
+        self.with_top_products += features.contains(SerpFeatures::TOP_PRODUCTS) as usize;
+        self.with_related_questions += features.contains(SerpFeatures::RELATED_QUESTIONS) as usize;
+        for result in &resp.organic_results {
+            let domain = normalize_domain(&result.domain);
+            let mut tally = match self.domains.get(&domain) {
+                Some(tally) => {
+                    self.by_appearances.remove(&(tally.appearances, domain.clone()));
+                    *tally
+                },
+                None if self.domains.len() >= self.domain_capacity => self.replace_least_seen(),
+                None => DomainTally::default(),
+            };
+            tally.appearances += 1;
+            tally.position_sum += result.position;
+            self.by_appearances.insert((tally.appearances, domain.clone()));
+            self.domains.insert(domain, tally);
+        }
+    }
+
+    /// the stats for every response added so far
+    pub fn summary(&self) -> CorpusStats {
+        let share = |count: usize| if self.responses == 0 { 0.0 } else { count as f64 / self.responses as f64 };
+        let mut top_domains: Vec<DomainStats> = self.domains.iter()
+            .map(|(domain, tally)| DomainStats {
+                domain: domain.clone(),
+                appearances: tally.appearances,
+                overcount: tally.overcount,
+                average_position: tally.position_sum as f64 / (tally.appearances - tally.overcount) as f64,
+            })
+            .collect();
+        top_domains.sort_by(|a, b| b.appearances.cmp(&a.appearances).then_with(|| a.domain.cmp(&b.domain)));
+        top_domains.truncate(TOP_DOMAINS);
+        CorpusStats {
+            responses: self.responses,
+            average_ads: share(self.ads),
+            share_with_ads: share(self.with_ads),
+            share_with_top_stories: share(self.with_top_stories),
+            share_with_top_products: share(self.with_top_products),
+            share_with_related_questions: share(self.with_related_questions),
+            top_domains,
+        }
+    }
+
+    /// forget the domain with the fewest appearances (the first alphabetically on a tie),
+    /// returning the tally a new domain starts from in its place
+    fn replace_least_seen(&mut self) -> DomainTally {
+        match self.by_appearances.pop_first() {
+            Some((appearances, domain)) => {
+                self.domains.remove(&domain);
+                DomainTally { appearances, overcount: appearances, position_sum: 0 }
+            },
+            None => DomainTally::default(),
+        }
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_json() -> serde_json::Value {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    /// the fixture without ads or top stories, keeping only organic positions 3 and 4
+    fn sparse() -> Resp {
+        let mut raw = fixture_json();
+        let raw = raw.as_object_mut().unwrap();
+        raw.remove("ads");
+        raw.remove("top_stories");
+        let organic = raw["organic_results"].as_array_mut().unwrap();
+        organic.retain(|result| result["position"].as_u64().unwrap() > 2);
+        serde_json::from_value(serde_json::Value::Object(raw.clone())).unwrap()
+    }
+
+    #[test]
+    fn corpus_stats() {
+        let mut accumulator = Accumulator::new();
+        accumulator.add(&serde_json::from_value(fixture_json()).unwrap());
+        accumulator.add(&sparse());
+        let stats = accumulator.summary();
+        assert_eq!(stats.responses, 2);
+        assert_eq!(stats.average_ads, 1.5);
+        assert_eq!(stats.share_with_ads, 0.5);
+        assert_eq!(stats.share_with_top_stories, 0.5);
+        assert_eq!(stats.share_with_top_products, 1.0);
+        let domains: Vec<(&str, usize, f64)> = stats.top_domains.iter()
+            .map(|d| (d.domain.as_str(), d.appearances, d.average_position))
+            .collect();
+        // samsung.com is at 2 and 4 in the first response (with and without "www.") and 4 in the second
+        assert_eq!(domains, vec![
+            ("samsung.com", 3, 10.0 / 3.0),
+            ("tomshardware.com", 2, 3.0),
+            ("pcmag.com", 1, 1.0),
+        ]);
+        assert!(serde_json::to_string(&stats).unwrap().contains("\"average_ads\":1.5"));
+        assert!(stats.top_domains.iter().all(|d| d.overcount == 0));
+
+        // an empty block is not the feature, as in SerpFeatures::of
+        let mut empty_ads = fixture_json();
+        empty_ads["ads"] = serde_json::json!([]);
+        accumulator.add(&serde_json::from_value(empty_ads).unwrap());
+        assert_eq!(accumulator.summary().share_with_ads, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn capped_domains() {
+        let mut accumulator = Accumulator::with_domain_capacity(2);
+        accumulator.add(&serde_json::from_value(fixture_json()).unwrap());
+        let domains: Vec<(String, usize, usize, f64)> = accumulator.summary().top_domains.into_iter()
+            .map(|d| (d.domain, d.appearances, d.overcount, d.average_position))
+            .collect();
+        // tomshardware.com (at 3) replaced pcmag.com (seen once at 1) and took over its count
+        assert_eq!(domains, vec![
+            ("samsung.com".to_string(), 2, 0, 3.0),
+            ("tomshardware.com".to_string(), 2, 1, 3.0),
+        ]);
+
+        // a domain that only turns up once the accumulator is full still gets in, in place of samsung.com
+        let mut late = fixture_json();
+        late["organic_results"].as_array_mut().unwrap().truncate(1);
+        late["organic_results"][0]["domain"] = "example.org".into();
+        accumulator.add(&serde_json::from_value(late).unwrap());
+        let domains: Vec<(String, usize, usize, f64)> = accumulator.summary().top_domains.into_iter()
+            .map(|d| (d.domain, d.appearances, d.overcount, d.average_position))
+            .collect();
+        assert_eq!(domains, vec![
+            ("example.org".to_string(), 3, 2, 1.0),
+            ("tomshardware.com".to_string(), 2, 1, 3.0),
+        ]);
+        assert_eq!(Accumulator::new().summary().average_ads, 0.0);
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+use crate::archive::stable_hash;
+
+/// Where the last seen suggestions for each seed are kept between runs
+pub trait SuggestionStore {
+    /// the suggestions last saved for seed, best first, or None if there are none yet
+    fn load(&self, seed: &str) -> io::Result<Option<Vec<String>>>;
+
+    /// replace the suggestions saved for seed
+    fn save(&self, seed: &str, suggestions: &[String]) -> io::Result<()>;
+}
+
+/// Keeps the suggestions for each seed in its own pretty-printed JSON file in a directory
+/// The format is stable and diffs cleanly, so the directory can be committed to git
+pub struct FileSuggestionStore {
+    dir: PathBuf,
+}
+
+/// The contents of one file written by FileSuggestionStore
+#[derive(Serialize, Deserialize, Debug)]
+struct StoredSuggestions {
+    seed: String,
+    suggestions: Vec<String>,
+}
+
+impl FileSuggestionStore {
+
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileSuggestionStore { dir: dir.into() }
+    }
+
+    /// the file the suggestions for seed are kept in, i.e. "acme_widgets-53a4b1bf.json" for "ACME widgets"
+    /// The readable part is shared by seeds like "acme-widgets" and "ACME_widgets", so a hash of the exact seed follows it
+    pub fn path(&self, seed: &str) -> PathBuf {
+        let seed = seed.trim();
+        let name: String = seed.to_lowercase().chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}-{:08x}.json", name, stable_hash(&[seed]) as u32))
+    }
+}
+
+impl SuggestionStore for FileSuggestionStore {
+    fn load(&self, seed: &str) -> io::Result<Option<Vec<String>>> {
+        let json = match fs::read_to_string(self.path(seed)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let stored: StoredSuggestions = serde_json::from_str(&json)?;
+        if stored.seed.trim() != seed.trim() {
+            let message = format!("{} holds the suggestions for {:?}, not {:?}", self.path(seed).display(), stored.seed, seed);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok(Some(stored.suggestions))
+    }
+
+    fn save(&self, seed: &str, suggestions: &[String]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let stored = StoredSuggestions { seed: seed.to_string(), suggestions: suggestions.to_vec() };
+        let mut json = serde_json::to_string_pretty(&stored)?;
+        json.push('\n');
+        fs::write(self.path(seed), json)
+    }
+}
+
+/// A suggestion and its position, starting at 1
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RankedSuggestion {
+    pub suggestion: String,
+    pub position: usize,
+}
+
+/// A suggestion that is still shown, but at a different position
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MovedSuggestion {
+    pub suggestion: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl MovedSuggestion {
+
+    /// how many places the suggestion moved up, negative if it moved down
+    pub fn delta(&self) -> isize {
+        self.from as isize - self.to as isize
+    }
+}
+
+/// How the suggestions for a seed changed since they were last seen
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SuggestionDiff {
+    pub added: Vec<RankedSuggestion>, // at their new position
+    pub removed: Vec<RankedSuggestion>, // at their old position
+    pub moved: Vec<MovedSuggestion>,
+}
+
+impl SuggestionDiff {
+
+    /// compare the suggestions last seen with the current ones, both best first
+    pub fn between(previous: &[String], current: &[String]) -> Self {
+        let position = |list: &[String], suggestion: &str| list.iter().position(|s| s == suggestion).map(|i| i + 1);
+        let mut diff = SuggestionDiff::default();
+        for (i, suggestion) in current.iter().enumerate() {
+            match position(previous, suggestion) {
+                None => diff.added.push(RankedSuggestion { suggestion: suggestion.clone(), position: i + 1 }),
+                Some(from) if from != i + 1 => diff.moved.push(MovedSuggestion { suggestion: suggestion.clone(), from, to: i + 1 }),
+                Some(_) => {},
+            }
+        }
+        for (i, suggestion) in previous.iter().enumerate() {
+            if position(current, suggestion).is_none() {
+                diff.removed.push(RankedSuggestion { suggestion: suggestion.clone(), position: i + 1 });
+            }
+        }
+        diff
+    }
+
+    /// true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+}
+
+/// Compare the current suggestions for seed with the ones in store, then save the current ones
+/// The first time a seed is tracked every suggestion is reported as added
+/// The crate has no autocomplete endpoint yet, so fetch the suggestions however you get them and pass them in
+pub fn track(seed: &str, current: &[String], store: &impl SuggestionStore) -> io::Result<SuggestionDiff> {
+    let previous = store.load(seed)?.unwrap_or_default();
+    let diff = SuggestionDiff::between(&previous, current);
+    store.save(seed, current)?;
+    Ok(diff)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn strings(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn diff_positions() {
+        let previous = strings(&["acme widgets", "acme login", "acme careers", "acme stock"]);
+        let current = strings(&["acme login", "acme widgets", "acme stock", "acme recall"]);
+        let diff = SuggestionDiff::between(&previous, &current);
+        assert_eq!(diff.added, vec![RankedSuggestion { suggestion: "acme recall".to_string(), position: 4 }]);
+        assert_eq!(diff.removed, vec![RankedSuggestion { suggestion: "acme careers".to_string(), position: 3 }]);
+        let moved: Vec<(&str, isize)> = diff.moved.iter().map(|m| (m.suggestion.as_str(), m.delta())).collect();
+        assert_eq!(moved, vec![("acme login", 1), ("acme widgets", -1), ("acme stock", 1)]);
+        assert!(SuggestionDiff::between(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn track_with_file_store() {
+        let dir = std::env::temp_dir().join(format!("scale_serp_suggestions_{}", std::process::id()));
+        let store = FileSuggestionStore::new(&dir);
+        assert_eq!(store.path("ACME widgets"), dir.join("acme_widgets-53a4b1bf.json"));
+        assert_eq!(store.path(" ACME widgets "), store.path("ACME widgets"));
+        let paths: HashSet<PathBuf> = ["acme widgets", "acme-widgets", "ACME_widgets"].iter().map(|seed| store.path(seed)).collect();
+        assert_eq!(paths.len(), 3);
+
+        let first = track("acme", &strings(&["acme login", "acme stock"]), &store).unwrap();
+        assert_eq!(first.added.len(), 2);
+        let second = track("acme", &strings(&["acme stock", "acme login"]), &store).unwrap();
+        assert!(second.added.is_empty() && second.removed.is_empty());
+        assert_eq!(second.moved.len(), 2);
+        assert_eq!(fs::read_to_string(store.path("acme")).unwrap(), "\
+{
+  \"seed\": \"acme\",
+  \"suggestions\": [
+    \"acme stock\",
+    \"acme login\"
+  ]
+}
+");
+
+        // a file that holds another seed's suggestions is refused rather than diffed against
+        fs::copy(store.path("acme"), store.path("acme widgets")).unwrap();
+        assert_eq!(track("acme widgets", &[], &store).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
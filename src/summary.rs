@@ -0,0 +1,76 @@
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+use crate::budget::parse_timestamp;
+use crate::link::normalize_domain;
+use crate::search::{Params, Resp};
+
+/// The few fields of a response that large-scale monitoring needs, for when storing every Resp is too much
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchSummary {
+    pub query: String,
+    pub location: String, // empty if the search was not geotargeted
+    pub total_results: usize,
+    pub top_3_domains: Vec<String>, // normalized, see normalize_domain, best first
+    pub has_ads: bool,
+    pub has_answer_box: bool, // a dictionary, weather, conversion, calculator, sports or finance answer
+    pub fetched_at: SystemTime,
+}
+
+impl Resp {
+
+    /// the summary of the response to the search for params
+    /// fetched_at is when ScaleSERP ran the search, or now if its created_at can not be parsed
+    pub fn summarize(&self, params: &Params) -> SearchSummary {
+        let mut organic: Vec<_> = self.organic_results.iter().collect();
+        organic.sort_by_key(|result| result.position);
+        SearchSummary {
+            query: params.q.clone(),
+            location: params.location.clone().unwrap_or_default(),
+            total_results: self.search_information.total_results,
+            top_3_domains: organic.iter().take(3).map(|result| normalize_domain(&result.domain)).collect(),
+            has_ads: !self.ads.is_empty(),
+            has_answer_box: self.dictionary_result.is_some()
+                || self.weather_result.is_some()
+                || self.conversion_result.is_some()
+                || self.calculator_result.is_some()
+                || self.sports_results.is_some()
+                || self.finance_result.is_some(),
+            fetched_at: parse_timestamp(&self.search_metadata.created_at).unwrap_or_else(SystemTime::now),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn fixture_json() -> serde_json::Value {
+        serde_json::from_str(include_str!("../fixtures/search_external_ssd.json")).unwrap()
+    }
+
+    #[test]
+    fn summarize() {
+        let resp: Resp = serde_json::from_value(fixture_json()).unwrap();
+        let params = Params::new("secret", "external ssd", "New York,New York,United States");
+        let summary = resp.summarize(&params);
+        assert_eq!(summary.query, "external ssd");
+        assert_eq!(summary.location, "New York,New York,United States");
+        assert_eq!(summary.total_results, resp.search_information.total_results);
+        assert_eq!(summary.top_3_domains, vec!["pcmag.com", "samsung.com", "tomshardware.com"]);
+        assert!(summary.has_ads && !summary.has_answer_box);
+        assert!(summary.fetched_at > UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(serde_json::from_str::<SearchSummary>(&json).unwrap(), summary);
+
+        let mut raw = fixture_json();
+        raw["calculator_result"] = serde_json::json!({"expression": "2+2", "result": "4"});
+        raw["ads"] = serde_json::json!([]);
+        let resp: Resp = serde_json::from_value(raw).unwrap();
+        let summary = resp.summarize(&Params::new("secret", "2+2", ""));
+        assert!(summary.has_answer_box && !summary.has_ads);
+        assert_eq!(summary.location, "");
+    }
+}
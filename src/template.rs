@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::error::Error;
+use crate::search::Params;
+
+/// A query with {key} placeholders, i.e. "best {product} under {price}"
+/// Write "{{" or "}}" for a literal brace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QueryTemplate {
+    pub template: String,
+}
+
+impl QueryTemplate {
+
+    pub fn new(template: &str) -> Self {
+        QueryTemplate { template: template.to_string() }
+    }
+
+    /// the query with every placeholder replaced by its value in vars
+    /// A placeholder without a value, or a brace that is never closed, is Error::InvalidQuery
+    pub fn render(&self, vars: &HashMap<&str, &str>) -> Result<String, Error> {
+        let mut rendered = String::with_capacity(self.template.len());
+        let mut chars = self.template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    rendered.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    rendered.push('}');
+                },
+                '{' => {
+                    let mut key = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => key.push(c),
+                            None => return Err(Error::InvalidQuery(format!("unclosed placeholder in {:?}", self.template))),
+                        }
+                    }
+                    let value = vars.get(key.trim())
+                        .ok_or_else(|| Error::InvalidQuery(format!("no value for {{{}}} in {:?}", key, self.template)))?;
+                    rendered.push_str(value);
+                },
+                '}' => return Err(Error::InvalidQuery(format!("unmatched '}}' in {:?}", self.template))),
+                c => rendered.push(c),
+            }
+        }
+        Ok(rendered)
+    }
+
+    /// a search for the rendered query, with every other setting (location, device, hl etc.) copied from base
+    pub fn into_params(&self, vars: &HashMap<&str, &str>, base: &Params) -> Result<Params, Error> {
+        let mut params = base.clone();
+        params.q = self.render(vars)?;
+        Ok(params)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let template = QueryTemplate::new("best {product} under { price }");
+        let vars: HashMap<&str, &str> = [("product", "external ssd"), ("price", "$100"), ("unused", "x")].into();
+        assert_eq!(template.render(&vars).unwrap(), "best external ssd under $100");
+
+        let params = template.into_params(&vars, &Params::new("secret", "", "United States").with_num(20)).unwrap();
+        assert_eq!(params.q, "best external ssd under $100");
+        assert_eq!((params.location.as_deref(), params.num), (Some("United States"), Some(20)));
+
+        assert_eq!(QueryTemplate::new("{{literal}} {product}").render(&vars).unwrap(), "{literal} external ssd");
+
+        let errors: Vec<String> = ["best {brand}", "best {product", "best product}"].iter()
+            .map(|template| QueryTemplate::new(template).render(&vars).unwrap_err().to_string())
+            .collect();
+        assert_eq!(errors, vec![
+            "invalid query: no value for {brand} in \"best {brand}\"",
+            "invalid query: unclosed placeholder in \"best {product\"",
+            "invalid query: unmatched '}' in \"best product}\"",
+        ]);
+    }
+}
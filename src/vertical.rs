@@ -0,0 +1,133 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The kind of Google search to run
+/// This is the one definition of the search_type values, used by Params, the client and response checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchType {
+    News,
+    Images,
+    Shopping,
+    Videos,
+    Places,
+}
+
+/// the block a regular web search puts its results in
+pub const WEB_RESULTS_BLOCK: &str = "organic_results";
+
+impl SearchType {
+
+    /// every search type
+    pub const ALL: [SearchType; 5] = [SearchType::News, SearchType::Images, SearchType::Shopping, SearchType::Videos, SearchType::Places];
+
+    /// the value of the search_type parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchType::News => "news",
+            SearchType::Images => "images",
+            SearchType::Shopping => "shopping",
+            SearchType::Videos => "videos",
+            SearchType::Places => "places",
+        }
+    }
+
+    /// the top-level block the results of this kind of search come back in
+    pub fn results_block(&self) -> &'static str {
+        match self {
+            SearchType::News => "news_results",
+            SearchType::Images => "image_results",
+            SearchType::Shopping => "shopping_results",
+            SearchType::Videos => "video_results",
+            SearchType::Places => "places_results",
+        }
+    }
+}
+
+impl fmt::Display for SearchType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SearchType {
+    type Err = String;
+
+    /// parse a search_type value, ignoring case and surrounding whitespace
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        SearchType::ALL.into_iter()
+            .find(|search_type| search_type.as_str() == name)
+            .ok_or_else(|| format!("unknown search type {:?}", s))
+    }
+}
+
+/// the results block expected for a search of search_type, None meaning a web search
+pub fn results_block(search_type: Option<SearchType>) -> &'static str {
+    search_type.map_or(WEB_RESULTS_BLOCK, |search_type| search_type.results_block())
+}
+
+/// A response whose results are for a different kind of search than was requested
+/// This usually means the search was routed to the wrong vertical
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerticalMismatch {
+    pub requested: Option<SearchType>, // None for a web search
+    pub expected_block: &'static str,
+    pub found_blocks: Vec<&'static str>, // the results blocks of other kinds of search that were in the response
+}
+
+impl fmt::Display for VerticalMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requested = self.requested.map_or("web", |search_type| search_type.as_str());
+        write!(f, "a {} search should return {}, but returned {}", requested, self.expected_block, self.found_blocks.join(", "))
+    }
+}
+
+/// check the raw payload of a search of kind requested for results from a different kind of search
+/// A response without any results block is not a mismatch, since a search can have no results
+pub fn check(raw: &serde_json::Value, requested: Option<SearchType>) -> Option<VerticalMismatch> {
+    let expected_block = results_block(requested);
+    let has_results = |block: &str| raw.get(block).is_some_and(|results| results.as_array().is_some_and(|r| !r.is_empty()));
+    if has_results(expected_block) {
+        return None;
+    }
+    let found_blocks: Vec<&'static str> = std::iter::once(WEB_RESULTS_BLOCK)
+        .chain(SearchType::ALL.iter().map(|search_type| search_type.results_block()))
+        .filter(|block| has_results(block))
+        .collect();
+    if found_blocks.is_empty() {
+        return None;
+    }
+    Some(VerticalMismatch { requested, expected_block, found_blocks })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for search_type in SearchType::ALL {
+            assert_eq!(search_type.as_str().parse::<SearchType>(), Ok(search_type));
+            assert_eq!(search_type.to_string(), search_type.as_str());
+        }
+        assert_eq!(" News ".parse::<SearchType>(), Ok(SearchType::News));
+        assert!("web".parse::<SearchType>().is_err());
+        let blocks: Vec<&str> = SearchType::ALL.iter().map(|search_type| search_type.results_block()).collect();
+        assert_eq!(blocks, vec!["news_results", "image_results", "shopping_results", "video_results", "places_results"]);
+    }
+
+    #[test]
+    fn mismatches() {
+        let web = serde_json::json!({"organic_results": [{"position": 1}]});
+        let news = serde_json::json!({"news_results": [{"position": 1}], "organic_results": []});
+        assert_eq!(check(&web, None), None);
+        assert_eq!(check(&news, Some(SearchType::News)), None);
+        assert_eq!(check(&serde_json::json!({"organic_results": []}), Some(SearchType::Images)), None);
+
+        let mismatch = check(&web, Some(SearchType::News)).unwrap();
+        assert_eq!(mismatch.found_blocks, vec!["organic_results"]);
+        assert_eq!(mismatch.to_string(), "a news search should return news_results, but returned organic_results");
+        assert_eq!(check(&news, None).unwrap().found_blocks, vec!["news_results"]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures_util::future::{self, Either, FutureExt};
+use crate::client::{ScaleSerpClient, SearchManyOptions};
+use crate::error::Error;
+use crate::market::MarketKey;
+use crate::search::{Params, Resp};
+
+/// The result of one search run by a SearchScheduler
+#[derive(Debug)]
+pub struct ResultEnvelope {
+    pub run: u64, // which run of the schedule this came from, starting at 1
+    pub params: Params,
+    pub market: MarketKey, // the market params were sent to, for grouping results
+    pub result: Result<Resp, Error>,
+    pub fetched_at: SystemTime,
+}
+
+/// Where a SearchScheduler sends every result, i.e. a database writer or archive::save
+pub trait ResultSink {
+    fn handle(&self, envelope: ResultEnvelope);
+}
+
+impl<F> ResultSink for F
+where
+    F: Fn(ResultEnvelope),
+{
+    fn handle(&self, envelope: ResultEnvelope) {
+        self(envelope)
+    }
+}
+
+/// Settings for a SearchScheduler
+#[derive(Debug, Clone, Copy)]
+pub struct SchedulerOptions {
+    pub interval: Duration, // from the start of one run to the start of the next
+    pub concurrency: usize, // how many searches of a run go at once
+    pub jitter: f64, // spread each wait by up to this fraction either way, i.e. 0.1 for ±10%
+    pub max_backoff: Duration, // the longest wait after runs where every search failed
+    pub grace_period: Duration, // how long running searches get to finish on shutdown
+}
+
+impl Default for SchedulerOptions {
+    fn default() -> Self {
+        SchedulerOptions {
+            interval: Duration::from_secs(3600),
+            concurrency: 4,
+            jitter: 0.1,
+            max_backoff: Duration::from_secs(6 * 3600),
+            grace_period: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What a SearchScheduler did before it was shut down
+#[derive(Debug, Default)]
+pub struct WorkerReport {
+    pub runs: u64,
+    pub searches: usize, // results handed to the sink
+    pub failures: usize, // results that were errors
+    pub unfinished: Vec<Params>, // searches of the last run that were cut short, to run first next time
+}
+
+/// Runs the same searches over and over on a schedule, handing every result to a sink
+/// Searches go through the client, so its min_interval, middleware and location fallback all apply.
+/// After a run where every search failed the wait doubles, up to max_backoff, until a search succeeds again.
+/// Searches ScaleSERP skipped because of a Google incident count as neither success nor failure
+pub struct SearchScheduler<'a> {
+    client: &'a ScaleSerpClient,
+    params: Vec<Params>,
+    options: SchedulerOptions,
+}
+
+impl<'a> SearchScheduler<'a> {
+
+    pub fn new(client: &'a ScaleSerpClient, params: Vec<Params>, options: SchedulerOptions) -> Self {
+        SearchScheduler { client, params, options }
+    }
+
+    /// run the schedule until shutdown completes, i.e. on ctrl-c
+    /// Searches already running when shutdown completes get the grace period to finish
+    pub async fn run_until<F>(&self, sink: &impl ResultSink, shutdown: F) -> WorkerReport
+    where
+        F: Future<Output = ()>,
+    {
+        let shutdown = shutdown.boxed_local().shared();
+        let mut jitter = Jitter::new();
+        let mut report = WorkerReport::default();
+        let mut failed_runs = 0;
+        loop {
+            let run_start = tokio::time::Instant::now();
+            report.runs += 1;
+            let options = SearchManyOptions { concurrency: self.options.concurrency, grace_period: self.options.grace_period, budget: None };
+            let outcome = self.client.search_many_until(&self.params, options, shutdown.clone()).await;
+            // skipped incidents are free and say nothing about whether searches work, so they do not count towards backoff
+            let mut counted = outcome.completed.iter().filter(|(_, result)| !matches!(result, Err(Error::SkippedIncident(_)))).peekable();
+            let all_failed = counted.peek().is_some() && counted.all(|(_, result)| result.is_err());
+            for (i, result) in outcome.completed {
+                report.searches += 1;
+                report.failures += result.is_err() as usize;
+                let params = self.params[i].clone();
+                sink.handle(ResultEnvelope { run: report.runs, market: MarketKey::from(&params), params, result, fetched_at: SystemTime::now() });
+            }
+            if shutdown.clone().now_or_never().is_some() {
+                let mut unfinished: Vec<usize> = outcome.abandoned.into_iter().chain(outcome.not_attempted).collect();
+                unfinished.sort_unstable();
+                report.unfinished = unfinished.into_iter().map(|i| self.params[i].clone()).collect();
+                return report;
+            }
+            failed_runs = if all_failed { failed_runs + 1 } else { 0 };
+            let wait = next_wait(&self.options, failed_runs, jitter.next());
+            if failed_runs > 0 {
+                log::warn!("every search in run {} failed, waiting {:?} before the next run", report.runs, wait);
+            }
+            let sleep = std::pin::pin!(tokio::time::sleep_until(run_start + wait));
+            if let Either::Right(_) = future::select(sleep, shutdown.clone()).await {
+                return report;
+            }
+        }
+    }
+}
+
+/// how long to wait before the next run: interval doubled for each failed run up to max_backoff,
+/// then spread by the jitter fraction. unit is a random number from -1 to 1
+fn next_wait(options: &SchedulerOptions, failed_runs: u32, unit: f64) -> Duration {
+    let backoff = options.interval.saturating_mul(2u32.saturating_pow(failed_runs));
+    let base = if failed_runs > 0 { backoff.min(options.max_backoff.max(options.interval)) } else { options.interval };
+    base.mul_f64((1.0 + options.jitter.clamp(0.0, 1.0) * unit).max(0.0))
+}
+
+/// Random numbers from -1 to 1 for spreading waits, so many workers started together drift apart
+/// This is xorshift seeded from the clock: fine for jitter, not for anything that needs real randomness
+struct Jitter {
+    state: u64,
+}
+
+impl Jitter {
+
+    fn new() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Jitter { state: nanos | 1 }
+    }
+
+    fn next(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::middleware::{ApiRequest, Middleware};
+
+    struct BlockAll;
+
+    impl Middleware for BlockAll {
+        fn before_request(&self, _request: &mut ApiRequest) -> Result<(), Error> {
+            Err(Error::Blocked("offline".to_string()))
+        }
+    }
+
+    /// answers every search as skipped because of a Google incident
+    struct SkipAll;
+
+    impl Middleware for SkipAll {
+        fn before_request(&self, _request: &mut ApiRequest) -> Result<(), Error> {
+            Err(Error::SkippedIncident("incident in progress".to_string()))
+        }
+    }
+
+    fn options(jitter: f64) -> SchedulerOptions {
+        SchedulerOptions {
+            interval: Duration::from_secs(10),
+            concurrency: 2,
+            jitter,
+            max_backoff: Duration::from_secs(40),
+            grace_period: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn waits() {
+        let options = options(0.1);
+        assert_eq!(next_wait(&options, 0, 0.0), Duration::from_secs(10));
+        assert_eq!(next_wait(&options, 1, 0.0), Duration::from_secs(20));
+        assert_eq!(next_wait(&options, 5, 0.0), Duration::from_secs(40));
+        assert_eq!(next_wait(&options, 0, 1.0), Duration::from_secs(11));
+        assert_eq!(next_wait(&options, 0, -1.0), Duration::from_secs(9));
+        let mut jitter = Jitter::new();
+        assert!((0..1000).map(|_| jitter.next()).all(|unit| (-1.0..=1.0).contains(&unit)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_and_shuts_down() {
+        let client = ScaleSerpClient::builder().api_key("demo").middleware(BlockAll).build().unwrap();
+        let params: Vec<Params> = ["a", "b"].iter().map(|q| Params::new("demo", q, "United States")).collect();
+        let scheduler = SearchScheduler::new(&client, params, options(0.0));
+        let runs = Mutex::new(Vec::new());
+        let sink = |envelope: ResultEnvelope| runs.lock().unwrap().push((envelope.run, tokio::time::Instant::now()));
+        let start = tokio::time::Instant::now();
+        // runs start at 0s, then after 20s and 40s waits: at 0, 20 and 60
+        let report = scheduler.run_until(&sink, tokio::time::sleep(Duration::from_secs(70))).await;
+        let starts: Vec<(u64, u64)> = runs.lock().unwrap().iter().map(|(run, at)| (*run, (*at - start).as_secs())).collect();
+        assert_eq!(starts, vec![(1, 0), (1, 0), (2, 20), (2, 20), (3, 60), (3, 60)]);
+        assert_eq!((report.runs, report.searches, report.failures), (3, 6, 6));
+        assert!(report.unfinished.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn interval_runs_from_start_to_start() {
+        // middleware can not wait, so the client's min_interval stands in for slow searches: each run takes 3s
+        let client = ScaleSerpClient::builder().api_key("demo").min_interval(Duration::from_secs(3)).middleware(BlockAll).build().unwrap();
+        let params: Vec<Params> = ["a", "b"].iter().map(|q| Params::new("demo", q, "United States")).collect();
+        let scheduler = SearchScheduler::new(&client, params, options(0.0));
+        let runs = Mutex::new(Vec::new());
+        let sink = |envelope: ResultEnvelope| runs.lock().unwrap().push((envelope.run, tokio::time::Instant::now()));
+        let start = tokio::time::Instant::now();
+        // results reach the sink when a run ends 3s after it started. The 20s and 40s waits count from
+        // each start, so runs end at 3, 23 and 63 rather than 3, 26 and 69
+        scheduler.run_until(&sink, tokio::time::sleep(Duration::from_secs(70))).await;
+        let starts: Vec<(u64, u64)> = runs.lock().unwrap().iter().map(|(run, at)| (*run, (*at - start).as_secs())).collect();
+        assert_eq!(starts, vec![(1, 3), (1, 3), (2, 23), (2, 23), (3, 63), (3, 63)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skipped_incidents_do_not_back_off() {
+        let client = ScaleSerpClient::builder().api_key("demo").middleware(SkipAll).build().unwrap();
+        let params = vec![Params::new("demo", "a", "United States")];
+        let scheduler = SearchScheduler::new(&client, params, options(0.0));
+        let runs = Mutex::new(Vec::new());
+        let sink = |envelope: ResultEnvelope| runs.lock().unwrap().push((envelope.run, tokio::time::Instant::now()));
+        let start = tokio::time::Instant::now();
+        let report = scheduler.run_until(&sink, tokio::time::sleep(Duration::from_secs(25))).await;
+        let starts: Vec<u64> = runs.lock().unwrap().iter().map(|(_, at)| (*at - start).as_secs()).collect();
+        assert_eq!(starts, vec![0, 10, 20]);
+        assert_eq!(report.runs, 3);
+    }
+}
@@ -0,0 +1,49 @@
+//! Measures how much Resp::from_json_with saves by capping long blocks while parsing.
+//! The counting allocator replaces the global one, so this lives in its own test binary
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use scale_serp::parse::ParseOptions;
+use scale_serp::search::Resp;
+
+struct Counting;
+
+thread_local! {
+    // per thread, so allocations by the test harness on other threads are not counted
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        BYTES.with(|bytes| bytes.set(bytes.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// the allocations and bytes allocated while parsing json with options
+fn measure(json: &str, options: &ParseOptions) -> (usize, usize) {
+    let before = (ALLOCATIONS.with(Cell::get), BYTES.with(Cell::get));
+    let resp = Resp::from_json_with(json, options).unwrap();
+    let after = (ALLOCATIONS.with(Cell::get), BYTES.with(Cell::get));
+    drop(resp);
+    (after.0 - before.0, after.1 - before.1)
+}
+
+#[test]
+fn capping_allocates_less() {
+    let json = include_str!("../fixtures/search_hundred_results.json");
+    let (allocations, bytes) = measure(json, &ParseOptions::default());
+    let (capped_allocations, capped_bytes) = measure(json, &ParseOptions { max_organic_results: Some(10), ..Default::default() });
+    // the 90 skipped results are never built, so capping must save at least most of their allocations
+    assert!(capped_allocations * 3 < allocations, "capped at 10: {} allocations, all 100: {}", capped_allocations, allocations);
+    assert!(capped_bytes * 2 < bytes, "capped at 10: {} bytes allocated, all 100: {}", capped_bytes, bytes);
+}